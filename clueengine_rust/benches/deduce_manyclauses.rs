@@ -0,0 +1,20 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use clueengine::{ClueEngine, CardUtils};
+
+fn deduce_single() {
+    // Player 1 refutes lots of suggestions without us seeing the card, so
+    // they end up with many overlapping clauses.
+    let mut clue_engine = ClueEngine::new(3, None).unwrap();
+    let suggestions = ["AGM", "BHN", "CIO", "DJP", "EKQ", "FLR", "AHO", "BIP", "CJQ", "DKR", "ELS", "FGT", "AIQ", "BJR", "CKS", "DLT"];
+    for suggestion in suggestions.iter() {
+        let cards = suggestion.chars().map(|ch| CardUtils::card_from_char(ch).unwrap()).collect::<Vec<_>>();
+        clue_engine.learn_suggest(0, cards[0], cards[1], cards[2], Some(1), None);
+    }
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    c.bench_function("deduce_manyclauses", |b| b.iter(|| deduce_single()));
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -22,8 +22,8 @@ fn process_request(request: &cgi::Request) -> Result<json::JsonValue, String> {
 fn process_query_string(query: &str) -> Result<json::JsonValue, String> {
     let query_parts: HashMap<String, String> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
     let action = query_parts.get("action").ok_or(String::from("Internal error - no action specified!"))?;
-    // Valid actions are 'new', 'whoOwns', 'suggestion', 'fullInfo', 'simulate' ('accusation' in the future?)
-    if action != "new" && action != "whoOwns" && action != "suggestion" && action != "fullInfo" && action != "simulate" {
+    // Valid actions are 'new', 'whoOwns', 'suggestion', 'fullInfo', 'simulate', 'opponents' ('accusation' in the future?)
+    if action != "new" && action != "whoOwns" && action != "suggestion" && action != "fullInfo" && action != "simulate" && action != "opponents" {
         return Err(format!("Internal error - invalid action \"{}\"!", action));
     }
     if action != "new" && !query_parts.contains_key("sess") {
@@ -42,11 +42,18 @@ fn process_query_string(query: &str) -> Result<json::JsonValue, String> {
                 .map_err(|_| format!("Internal error - action new can't parse numCards{} value \"{}\"!", i, number_of_cards_str))?;
             number_of_cards.push(real_number);
         }
-        let engine = clueengine::ClueEngine::new(num_players, Some(&number_of_cards))?;
+        let mut engine = clueengine::ClueEngine::new(num_players, Some(&number_of_cards))?;
+        // If we know which player we are, keep track of what the others know.
+        if let Some(my_player) = parse_optional_query_part::<usize>(&query_parts, "myPlayer")? {
+            engine.track_opponents(my_player).map_err(|e| format!("Internal error - {}", e))?;
+        }
         return Ok(json::object! {"session": engine.write_to_string()});
     }
 
-    let mut engine = clueengine::ClueEngine::load_from_string(query_parts.get("sess").unwrap())
+    // Clients can ask us to check session strings they didn't get from us.
+    let validate = parse_optional_query_part::<bool>(&query_parts, "validate")?.unwrap_or(false);
+    let load = if validate { clueengine::ClueEngine::load_from_string_and_validate } else { clueengine::ClueEngine::load_from_string };
+    let mut engine = load(query_parts.get("sess").unwrap())
         .map_err(|x|format!("Internal error - invalid session string '{}': error \"{}\"", query_parts.get("sess").unwrap(), x))?;
 
     if action == "whoOwns" {
@@ -56,9 +63,9 @@ fn process_query_string(query: &str) -> Result<json::JsonValue, String> {
             return Err(String::from("Internal error - owner out of range!"));
         }
         let card = card_from_query_parts(&query_parts, "card")?;
-        let changed_cards = engine.learn_info_on_card(owner as usize, card, true, true);
+        let change_set = engine.learn_info_on_card(owner as usize, card, true, true);
         return Ok(json::object! {
-            "newInfo": get_info_from_changed_cards(&engine, &changed_cards),
+            "newInfo": get_info_from_change_set(&engine, &change_set),
             "clauseInfo": get_clause_info(&engine),
             "session": engine.write_to_string(),
             "isConsistent": engine.is_consistent()
@@ -80,9 +87,9 @@ fn process_query_string(query: &str) -> Result<json::JsonValue, String> {
         }
         let refuting_player = if refuting_player_number == -1 { None } else { Some(refuting_player_number as usize)};
         let refuting_card = optional_card_from_query_parts(&query_parts, "refutingCard")?;
-        let changed_cards = engine.learn_suggest(suggesting_player as usize, card1, card2, card3, refuting_player, refuting_card);
+        let change_set = engine.learn_suggest(suggesting_player as usize, card1, card2, card3, refuting_player, refuting_card);
         return Ok(json::object! {
-            "newInfo": get_info_from_changed_cards(&engine, &changed_cards),
+            "newInfo": get_info_from_change_set(&engine, &change_set),
             "clauseInfo": get_clause_info(&engine),
             "session": engine.write_to_string(),
             "isConsistent": engine.is_consistent()
@@ -94,7 +101,7 @@ fn process_query_string(query: &str) -> Result<json::JsonValue, String> {
         // Don't return number_of_cards for solution
         number_of_cards.remove(number_of_cards.len() - 1);
         return Ok(json::object! {
-            "newInfo": get_info_from_changed_cards(&engine, &all_cards),
+            "newInfo": get_info_from_cards(&engine, &all_cards),
             "clauseInfo": get_clause_info(&engine),
             "session": engine.write_to_string(),
             "numPlayers": engine.number_of_real_players(),
@@ -103,12 +110,30 @@ fn process_query_string(query: &str) -> Result<json::JsonValue, String> {
         });
     }
     if action == "simulate" {
-        let simulation_data = engine.do_simulation(false);
+        let config = simulation_config_from_query_parts(&query_parts)?;
+        let refine = parse_optional_query_part::<bool>(&query_parts, "refine")?.unwrap_or(false);
+        let simulation_data = match std::env::var(SIMULATION_CACHE_ENV_VAR) {
+            Ok(cache_dir) => simulate_with_cache_dir(&cache_dir, &engine, &config, refine)?,
+            Err(_) => engine.do_simulation_with_config(&config)
+        };
         return Ok(json::object! {
             "simData": format_simulation_data(&simulation_data.0),
             "totalNumSimulations": simulation_data.1
         });
     }
+    if action == "opponents" {
+        let models = engine.opponent_models().ok_or("Internal error - not tracking opponents (start the game with myPlayer)")?;
+        let mut opponents = json::array![];
+        for (player_index, progress) in models.opponents_by_progress() {
+            opponents.push(json::object!{
+                "player": player_index,
+                "progress": progress,
+                "remainingSolutions": models.remaining_solutions(player_index).unwrap(),
+                "knownSolutionCards": models.known_solution_cards(player_index).unwrap().into_iter().map(card_to_string).collect::<Vec<String>>()
+            }).unwrap();
+        }
+        return Ok(json::object! {"opponents": opponents});
+    }
     return Err(format!("Internal error - unexpected action \"{}\"", action));
 }
 
@@ -126,9 +151,14 @@ fn get_clause_info(engine: &clueengine::ClueEngine) -> json::JsonValue {
     info
 }
 
-fn get_info_from_changed_cards(engine: &clueengine::ClueEngine, changed_cards: &clueengine::CardSet) -> json::JsonValue {
+// Only the cards we learned something new about are sent back.
+fn get_info_from_change_set(engine: &clueengine::ClueEngine, change_set: &clueengine::ChangeSet) -> json::JsonValue {
+    return get_info_from_cards(engine, &change_set.cards());
+}
+
+fn get_info_from_cards(engine: &clueengine::ClueEngine, cards: &clueengine::CardSet) -> json::JsonValue {
     let mut info = json::array![];
-    for card in changed_cards.iter() {
+    for card in cards.iter() {
         let possible_owners = engine.who_has_card(*card);
         let status = if possible_owners.len() == 1 {
             if *possible_owners.iter().next().unwrap() == engine.number_of_real_players() {
@@ -162,6 +192,118 @@ fn format_simulation_data(simulation_data: &HashMap<clueengine::Card, Vec<usize>
     return data;
 }
 
+// All of these are optional; anything missing gets the usual default.  Anyone
+// can send us these, so the ones that make a simulation take longer are
+// capped, and the number of threads is up to the server.
+const MAX_NUM_SIMULATIONS: i32 = 100000;
+const MAX_MAX_ITERATIONS: i32 = 100;
+const MAX_MIN_ACCEPTED_SIMULATIONS: usize = 1000;
+
+fn simulation_config_from_query_parts(query_parts: &HashMap<String, String>) -> Result<clueengine::SimulationConfig, String> {
+    let mut builder = clueengine::SimulationConfig::builder();
+    if let Some(strategy_str) = query_parts.get("strategy") {
+        let strategy = clueengine::SamplingStrategy::from_name(strategy_str).map_err(|e| format!("Internal error - {}", e))?;
+        builder = builder.strategy(strategy);
+    }
+    if let Some(value) = parse_optional_query_part::<i32>(query_parts, "numSimulations")? {
+        builder = builder.num_simulations(std::cmp::min(value, MAX_NUM_SIMULATIONS));
+    }
+    if let Some(value) = parse_optional_query_part::<i32>(query_parts, "chunkSize")? {
+        builder = builder.chunk_size(value);
+    }
+    if let Some(value) = parse_optional_query_part::<i32>(query_parts, "maxIterations")? {
+        builder = builder.max_iterations(std::cmp::min(value, MAX_MAX_ITERATIONS));
+    }
+    if let Some(value) = parse_optional_query_part::<usize>(query_parts, "minAcceptedSimulations")? {
+        builder = builder.min_accepted_simulations(std::cmp::min(value, MAX_MIN_ACCEPTED_SIMULATIONS));
+    }
+    if let Some(value) = parse_optional_query_part::<bool>(query_parts, "parallel")? {
+        builder = builder.parallel(value);
+    }
+    return builder.build().map_err(|e| format!("Internal error - {}", e));
+}
+
+// If this is set, simulation results are kept in this directory (one file for
+// each position) so the same position can be answered right away next time.
+const SIMULATION_CACHE_ENV_VAR: &str = "CLUESOLVER_SIMULATION_CACHE_DIR";
+
+fn simulate_with_cache_dir(cache_dir: &str, engine: &clueengine::ClueEngine, config: &clueengine::SimulationConfig, refine: bool) -> Result<(HashMap<clueengine::Card, Vec<usize>>, i32), String> {
+    let cache_path = std::path::Path::new(cache_dir).join(clueengine::SimulationCache::file_name(engine, config.strategy()));
+    // Another request is working on this position.  If it takes too long,
+    // do without the cache rather than keep waiting.
+    let lock = match CacheFileLock::acquire(&cache_path) {
+        Some(lock) => lock,
+        None => return Ok(engine.do_simulation_with_config(config))
+    };
+    // A missing or unreadable file just means starting over.
+    let mut cache = clueengine::SimulationCache::new();
+    if let Ok(contents) = std::fs::read_to_string(&cache_path) {
+        if cache.load_entry_from_string(&contents, engine, config.strategy()).is_err() {
+            cache = clueengine::SimulationCache::new();
+        }
+    }
+    if !refine && cache.has_enough_simulations(engine, config) {
+        return Ok(cache.get(engine, config.strategy()).unwrap());
+    }
+    let simulation_data = cache.refine(engine, config);
+    // Write to a temporary file and rename it so nobody ever reads a partly
+    // written file.
+    let temp_path = lock.path.with_extension("tmp");
+    std::fs::write(&temp_path, cache.write_to_string()).map_err(|e| format!("Internal error - couldn't write simulation cache: {}", e))?;
+    std::fs::rename(&temp_path, &cache_path).map_err(|e| format!("Internal error - couldn't write simulation cache: {}", e))?;
+    return Ok(simulation_data);
+}
+
+// Only one request at a time gets to read and write a cache file.  This is a
+// separate file that only exists while someone holds the lock.
+struct CacheFileLock {
+    path: std::path::PathBuf,
+}
+
+impl CacheFileLock {
+    const WAIT: std::time::Duration = std::time::Duration::from_millis(50);
+    const MAX_WAIT: std::time::Duration = std::time::Duration::from_secs(30);
+    // Nothing should hold the lock this long, so whoever did must have died.
+    const STALE_AGE: std::time::Duration = std::time::Duration::from_secs(300);
+
+    fn acquire(cache_path: &std::path::Path) -> Option<CacheFileLock> {
+        let path = cache_path.with_extension("lock");
+        let start = std::time::Instant::now();
+        loop {
+            match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Some(CacheFileLock { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let is_stale = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age > Self::STALE_AGE);
+                    if is_stale {
+                        let _ = std::fs::remove_file(&path);
+                        continue;
+                    }
+                    if start.elapsed() > Self::MAX_WAIT {
+                        return None;
+                    }
+                    std::thread::sleep(Self::WAIT);
+                }
+                Err(_) => return None
+            }
+        }
+    }
+}
+
+impl Drop for CacheFileLock {
+    fn drop(self: &mut Self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn parse_optional_query_part<T: std::str::FromStr>(query_parts: &HashMap<String, String>, key: &str) -> Result<Option<T>, String> {
+    return match query_parts.get(key) {
+        None => Ok(None),
+        Some(value_str) => value_str.parse::<T>().map(|value| Some(value)).map_err(|_| format!("Internal error - couldn't parse {} \"{}\"", key, value_str))
+    };
+}
+
 fn card_from_query_parts(query_parts: &HashMap<String, String>, key: &str) -> Result<clueengine::Card, String> {
     let card_str = query_parts.get(key).ok_or(format!("Internal error - missing card with key {}!", key))?;
     return card_from_string(card_str).map_err(|_| format!("Internal error - bad card string {} for key {}", card_str, key));
@@ -220,6 +362,14 @@ mod tests {
             "{\"session\": \"54-.4-.4-.3-.3-.3-.\"}");
     }
 
+    #[test]
+    fn test_new_with_myPlayer_tracks_opponents() {
+        assert_querystring_results_match(
+            "action=new&players=3&numCards0=6&numCards1=6&numCards2=6&myPlayer=0",
+            "{\"session\": \"36-.6-.6-.3-.|0\"}");
+        assert!(process_query_string("action=new&players=3&numCards0=6&numCards1=6&numCards2=6&myPlayer=3").is_err());
+    }
+
     #[test]
     fn test_whoOwns_no_sess_error() {
         let result = process_query_string("action=whoOwns&owner=0&card=ProfessorPlum");
@@ -275,6 +425,13 @@ mod tests {
             r#"{"newInfo": [{"card": "ProfessorPlum", "status": 1, "owner": [0]}], "clauseInfo": {}, "session": "63A-.3-A.3-A.3-A.3-A.3-A.3-A.", "isConsistent": true}"#);
     }
 
+    #[test]
+    fn test_whoOwns_already_known() {
+        assert_querystring_results_match(
+            "sess=63A-.3-A.3-A.3-A.3-A.3-A.3-A.&action=whoOwns&owner=0&card=ProfessorPlum",
+            r#"{"newInfo": [], "clauseInfo": {}, "session": "63A-.3-A.3-A.3-A.3-A.3-A.3-A.", "isConsistent": true}"#);
+    }
+
     #[test]
     fn test_whoOwns_solutionOwns() {
         assert_querystring_results_match(
@@ -432,6 +589,14 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_fullInfo_validate_rejects_invalid_sess() {
+        // The hands only add up to 17 cards.
+        assert!(process_query_string("action=fullInfo&sess=35-.6-.6-.3-.").is_ok());
+        assert!(process_query_string("action=fullInfo&sess=35-.6-.6-.3-.&validate=true").is_err());
+        assert!(process_query_string("action=fullInfo&sess=36-.6-.6-.3-.&validate=true").is_ok());
+    }
+
     #[test]
     #[ignore] // This test is slow
     fn test_simulate_complicated_game() {
@@ -451,12 +616,89 @@ mod tests {
         assert!(result["simData"]["Ballroom"][0].as_i32().unwrap() > 900);
     }
 
+    #[test]
+    fn test_opponents_after_suggestion() {
+        let result = process_query_string("action=suggestion&sess=36-.6-.6-.3-.|0&suggestingPlayer=2&card1=ProfessorPlum&card2=Knife&card3=Hall&refutingPlayer=0&refutingCard=Knife").unwrap();
+        let session = result["session"].as_str().unwrap();
+        assert!(session.ends_with("|02AGM0G"));
+        let result = process_query_string(&format!("action=opponents&sess={}", session)).unwrap();
+        assert_eq!(2, result["opponents"].len());
+        // Only player 2 saw the Knife.
+        assert_eq!(2, result["opponents"][0]["player"]);
+        assert_eq!(270, result["opponents"][0]["remainingSolutions"]);
+        assert_eq!(1, result["opponents"][1]["player"]);
+        assert_eq!(324, result["opponents"][1]["remainingSolutions"]);
+    }
+
+    #[test]
+    fn test_opponents_not_tracking_error() {
+        let result = process_query_string("action=opponents&sess=36-.6-.6-.3-.");
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_simulate_sess_missing_error() {
         let result = process_query_string("action=simulate");
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_simulate_with_config() {
+        let result = process_query_string("action=simulate&sess=63A-.3-A.3-A.3-A.3-A.3-A.3-A.&strategy=RandomSolutions&numSimulations=500&parallel=false").unwrap();
+        assert_eq!(500, result["totalNumSimulations"].as_i32().unwrap());
+        assert_eq!(0, result["simData"]["ProfessorPlum"][1]);
+    }
+
+    #[test]
+    fn test_simulate_strategy_invalid_error() {
+        let result = process_query_string("action=simulate&sess=63-.3-.3-.3-.3-.3-.3-.&strategy=NotAStrategy");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_simulate_numSimulations_invalid_error() {
+        assert!(process_query_string("action=simulate&sess=63-.3-.3-.3-.3-.3-.3-.&numSimulations=many").is_err());
+        assert!(process_query_string("action=simulate&sess=63-.3-.3-.3-.3-.3-.3-.&numSimulations=0").is_err());
+    }
+
+    #[test]
+    fn test_simulation_config_caps_expensive_settings() {
+        let query_parts: HashMap<String, String> = url::form_urlencoded::parse("numSimulations=100000000&maxIterations=100000&minAcceptedSimulations=100000000&numThreads=1000".as_bytes()).into_owned().collect();
+        let config = simulation_config_from_query_parts(&query_parts).unwrap();
+        assert_eq!(MAX_NUM_SIMULATIONS, config.num_simulations());
+        assert_eq!(MAX_MAX_ITERATIONS, config.max_iterations());
+        assert_eq!(MAX_MIN_ACCEPTED_SIMULATIONS, config.min_accepted_simulations());
+        assert_eq!(None, config.num_threads());
+    }
+
+    #[test]
+    fn test_simulate_with_cache_dir() {
+        let cache_dir = std::env::temp_dir().join(format!("cluesolver_cache_test_{}", std::process::id()));
+        std::fs::create_dir_all(&cache_dir).unwrap();
+        let engine = clueengine::ClueEngine::load_from_string("63A-.3-A.3-A.3-A.3-A.3-A.3-A.").unwrap();
+        let config = clueengine::SimulationConfig::builder().strategy(clueengine::SamplingStrategy::RandomSolutions).num_simulations(200).min_accepted_simulations(1).parallel(false).build().unwrap();
+        let cache_path = cache_dir.join(clueengine::SimulationCache::file_name(&engine, config.strategy()));
+        let first = simulate_with_cache_dir(cache_dir.to_str().unwrap(), &engine, &config, false).unwrap();
+        let modified = std::fs::metadata(&cache_path).unwrap().modified().unwrap();
+        // A hit doesn't write the file again.
+        assert_eq!(first, simulate_with_cache_dir(cache_dir.to_str().unwrap(), &engine, &config, false).unwrap());
+        assert_eq!(modified, std::fs::metadata(&cache_path).unwrap().modified().unwrap());
+        assert_eq!(400, simulate_with_cache_dir(cache_dir.to_str().unwrap(), &engine, &config, true).unwrap().1);
+        // The lock is gone once we're done.
+        assert!(!cache_path.with_extension("lock").exists());
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_file_lock() {
+        let cache_path = std::env::temp_dir().join(format!("cluesolver_lock_test_{}.txt", std::process::id()));
+        let lock = CacheFileLock::acquire(&cache_path).unwrap();
+        assert!(lock.path.exists());
+        drop(lock);
+        assert!(!cache_path.with_extension("lock").exists());
+        assert!(CacheFileLock::acquire(&cache_path).is_some());
+    }
+
     #[test]
     fn test_normalize_new_info() {
         let mut result = json::parse(r#"{"newInfo": [{"card": "z", "status": 0, "owner": [0]}, {"card": "a", "status": 1, "owner": [1]}]}"#).unwrap();
@@ -0,0 +1,37 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use clueengine::{ClueCnf, ClueEngine};
+
+// Usage:
+//   dimacs <session string>
+//     prints the game's constraints in DIMACS CNF format
+//   dimacs <session string> <model file>
+//     reads a satisfying assignment from another SAT solver and prints the
+//     session string of the deal it describes
+fn main() {
+    let args = env::args().collect::<Vec<String>>();
+    if args.len() != 2 && args.len() != 3 {
+        eprintln!("Usage: {} <session string> [model file]", args[0]);
+        process::exit(1);
+    }
+    if let Err(e) = run(&args[1], args.get(2)) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn run(session: &str, model_file: Option<&String>) -> Result<(), String> {
+    let engine = ClueEngine::load_from_string(session)?;
+    let cnf = ClueCnf::from_engine(&engine);
+    match model_file {
+        None => print!("{}", cnf.write_to_dimacs()),
+        Some(model_file) => {
+            let model_string = fs::read_to_string(model_file).map_err(|e| format!("Couldn't read {}: {}", model_file, e))?;
+            let model = cnf.parse_model(&model_string)?;
+            println!("{}", cnf.engine_from_model(&model)?.write_to_string());
+        }
+    }
+    return Ok(());
+}
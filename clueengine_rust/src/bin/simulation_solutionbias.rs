@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use clueengine::{ClueEngine, Card};
+use clueengine::{ClueEngine, Card, SamplingStrategy};
 
 fn main() {
     let engine = ClueEngine::load_from_string("36-.6--AHT.6-.3-.").unwrap();
@@ -8,6 +8,8 @@ fn main() {
     print_stats(&old_simulation, "Old");
     let new_simulation = engine.do_simulation(true);
     print_stats(&new_simulation, "New");
+    let exact_simulation = engine.do_simulation_with_strategy(SamplingStrategy::ExactUniform);
+    print_stats(&exact_simulation, "Exact");
 }
 
 fn print_stats(simulation: &(HashMap<Card, Vec<usize>>, i32), description: &str) {
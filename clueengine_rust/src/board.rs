@@ -0,0 +1,394 @@
+// The game board: rooms, the corridor squares between them, doors, where each
+// suspect starts, and the secret passages between corner rooms.
+//
+// A token is either on a corridor square or in a room.  Each step moves to a
+// neighboring corridor square, or through a door into (or out of) a room.
+// Going into a room ends the move, so paths never go through rooms.  Taking
+// a secret passage uses up a whole turn instead of rolling.
+//
+// To keep things simple, a roll of N lets a token move up to N steps (the
+// rules say exactly N, but on this board there's almost always a way to
+// waste steps), and other tokens never get in the way.
+
+use crate::{Card, CardType, CardUtils};
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Eq, PartialEq, Debug, Hash, Copy, Clone)]
+pub enum BoardPosition {
+    Square { row: usize, column: usize },
+    Room(Card),
+}
+
+#[derive(Clone, Debug)]
+pub struct Board {
+    // How many six-sided dice are rolled each turn.
+    pub num_dice: usize,
+    positions: Vec<BoardPosition>,
+    position_indices: HashMap<BoardPosition, usize>,
+    neighbors: Vec<Vec<usize>>,
+    // distances[from][to] is the fewest steps, or None if there's no way.
+    distances: Vec<Vec<Option<usize>>>,
+    secret_passages: HashMap<Card, Card>,
+    start_positions: HashMap<Card, BoardPosition>,
+}
+
+// '#' is off the board (or the middle, where the solution is kept), '.' is a
+// corridor square, and a room's card letter (see CardUtils::card_from_char())
+// is part of that room.  A lowercase letter is a room square with a door,
+// which connects to the corridor squares next to it.
+const STANDARD_LAYOUT: [&str; 25] = [
+    "#########.####.#########",
+    "PPPPPP#...SSSS...#NNNNNN",
+    "PPPPPP..SSSSSSSS..NNNNNN",
+    "PPPPPP..SSSSSSSS..NNNNNN",
+    "PPPPPP..sSSSSSSs..nNNNNN",
+    "PPPPPP..SSSSSSSS...NNNN#",
+    "#PPPpP..SsSSSSsS........",
+    "#......................#",
+    "OOOOO.............UUUUUU",
+    "OOOOOOOO..#####...uUUUUU",
+    "OOOOOOOO..#####...UUUUUU",
+    "OOOOOOOo..#####...UUUUUU",
+    "OOOOOOOO..#####...UUUUuU",
+    "OOOOOOOO..#####.........",
+    "OOOOOOoO..#####..RRrRRRR",
+    "#.........#####..RRRRRRR",
+    ".................rRRRRRR",
+    "#................RRRRRRR",
+    "#........MMmmMM.........",
+    "TTTTTTt..MMMMMM........#",
+    "TTTTTTT..MMMMMM........#",
+    "TTTTTTT..MMMMMm..qQQQQQQ",
+    "TTTTTTT..MMMMMM..QQQQQQQ",
+    "TTTTTTT..MMMMMM..QQQQQQQ",
+    "TTTTTTT.#MMMMMM##QQQQQQQ",
+];
+
+const STANDARD_START_SQUARES: [(Card, usize, usize); 6] = [
+    (Card::ProfessorPlum, 18, 23),
+    (Card::ColonelMustard, 16, 0),
+    (Card::MrGreen, 0, 14),
+    (Card::MissScarlet, 24, 7),
+    (Card::DrOrchid, 0, 9),
+    (Card::MrsPeacock, 6, 23),
+];
+
+const STANDARD_SECRET_PASSAGES: [(Card, Card); 2] = [
+    (Card::Kitchen, Card::Study),
+    (Card::Conservatory, Card::Lounge),
+];
+
+impl Board {
+    // The usual mansion, played with one die.
+    pub fn standard() -> Board {
+        return Board::from_layout(&STANDARD_LAYOUT, &STANDARD_START_SQUARES, &STANDARD_SECRET_PASSAGES, 1).unwrap();
+    }
+
+    // See STANDARD_LAYOUT for what layout looks like.  Secret passages go
+    // both ways.
+    pub fn from_layout(layout: &[&str], start_squares: &[(Card, usize, usize)], secret_passages: &[(Card, Card)], num_dice: usize) -> Result<Board, String> {
+        if num_dice == 0 {
+            return Err(String::from("num_dice must be positive"));
+        }
+        let grid = layout.iter().map(|row| row.chars().collect::<Vec<char>>()).collect::<Vec<Vec<char>>>();
+        let mut positions = CardUtils::cards_of_type(CardType::Room).map(BoardPosition::Room).collect::<Vec<BoardPosition>>();
+        for (row, row_chars) in grid.iter().enumerate() {
+            for (column, ch) in row_chars.iter().enumerate() {
+                match ch {
+                    '.' => positions.push(BoardPosition::Square { row, column }),
+                    '#' => {},
+                    _ => {
+                        let card = CardUtils::card_from_char(ch.to_ascii_uppercase())?;
+                        if CardUtils::card_type(card) != CardType::Room {
+                            return Err(format!("'{}' at row {} column {} isn't a room", ch, row, column));
+                        }
+                    }
+                }
+            }
+        }
+        let position_indices = positions.iter().enumerate().map(|(index, position)| (*position, index)).collect::<HashMap<BoardPosition, usize>>();
+
+        let mut neighbors = vec![vec![]; positions.len()];
+        let mut connect = |a: BoardPosition, b: BoardPosition| {
+            let (a_index, b_index) = (position_indices[&a], position_indices[&b]);
+            if !neighbors[a_index].contains(&b_index) {
+                neighbors[a_index].push(b_index);
+                neighbors[b_index].push(a_index);
+            }
+        };
+        for (row, row_chars) in grid.iter().enumerate() {
+            for (column, ch) in row_chars.iter().enumerate() {
+                // Only look right and down so each pair is seen once.
+                for (other_row, other_column) in [(row, column + 1), (row + 1, column)] {
+                    let other_ch = match grid.get(other_row).and_then(|other_row_chars| other_row_chars.get(other_column)) {
+                        Some(other_ch) => *other_ch,
+                        None => continue
+                    };
+                    let square = BoardPosition::Square { row, column };
+                    let other_square = BoardPosition::Square { row: other_row, column: other_column };
+                    if *ch == '.' && other_ch == '.' {
+                        connect(square, other_square);
+                    } else if *ch == '.' && other_ch.is_ascii_lowercase() {
+                        connect(square, BoardPosition::Room(CardUtils::card_from_char(other_ch.to_ascii_uppercase())?));
+                    } else if ch.is_ascii_lowercase() && other_ch == '.' {
+                        connect(BoardPosition::Room(CardUtils::card_from_char(ch.to_ascii_uppercase())?), other_square);
+                    }
+                }
+            }
+        }
+
+        let mut start_positions = HashMap::new();
+        for (suspect, row, column) in start_squares.iter() {
+            let position = BoardPosition::Square { row: *row, column: *column };
+            if !position_indices.contains_key(&position) {
+                return Err(format!("Start square at row {} column {} isn't a corridor square", row, column));
+            }
+            start_positions.insert(*suspect, position);
+        }
+        let mut passages = HashMap::new();
+        for (room1, room2) in secret_passages.iter() {
+            passages.insert(*room1, *room2);
+            passages.insert(*room2, *room1);
+        }
+
+        let mut board = Board {
+            num_dice,
+            positions,
+            position_indices,
+            neighbors,
+            distances: vec![],
+            secret_passages: passages,
+            start_positions,
+        };
+        board.distances = (0..board.positions.len()).map(|index| board.distances_from_index(index)).collect();
+        return Ok(board);
+    }
+
+    // Breadth-first search that doesn't go through rooms.
+    fn distances_from_index(self: &Self, from_index: usize) -> Vec<Option<usize>> {
+        let mut distances = vec![None; self.positions.len()];
+        distances[from_index] = Some(0);
+        let mut queue = VecDeque::new();
+        queue.push_back(from_index);
+        while let Some(index) = queue.pop_front() {
+            if index != from_index && matches!(self.positions[index], BoardPosition::Room(_)) {
+                continue;
+            }
+            let distance = distances[index].unwrap();
+            for neighbor in self.neighbors[index].iter() {
+                if distances[*neighbor] == None {
+                    distances[*neighbor] = Some(distance + 1);
+                    queue.push_back(*neighbor);
+                }
+            }
+        }
+        return distances;
+    }
+
+    // Where position is in positions().
+    pub(crate) fn index_of(self: &Self, position: BoardPosition) -> usize {
+        return *self.position_indices.get(&position).unwrap_or_else(|| panic!("{:?} isn't on the board", position));
+    }
+
+    // Indexed like positions().
+    pub(crate) fn distances_from(self: &Self, from_index: usize) -> &[Option<usize>] {
+        return &self.distances[from_index];
+    }
+
+    pub fn start_position(self: &Self, suspect: Card) -> Option<BoardPosition> {
+        return self.start_positions.get(&suspect).copied();
+    }
+
+    pub fn secret_passage(self: &Self, room: Card) -> Option<Card> {
+        return self.secret_passages.get(&room).copied();
+    }
+
+    // Every room and corridor square on the board.
+    pub fn positions(self: &Self) -> &[BoardPosition] {
+        return &self.positions;
+    }
+
+    // The fewest steps to get from one place to another, not counting
+    // secret passages.
+    pub fn distance(self: &Self, from: BoardPosition, to: BoardPosition) -> Option<usize> {
+        return self.distances[self.index_of(from)][self.index_of(to)];
+    }
+
+    // Everywhere a token can stop after rolling this number.
+    pub fn positions_reachable(self: &Self, from: BoardPosition, roll: usize) -> Vec<BoardPosition> {
+        return self.distances[self.index_of(from)].iter().enumerate()
+            .filter(|(_, distance)| distance.is_some_and(|distance| distance >= 1 && distance <= roll))
+            .map(|(index, _)| self.positions[index])
+            .collect();
+    }
+
+    // The rooms a token can be in at the end of this turn with this roll,
+    // including through a secret passage.
+    pub fn rooms_reachable(self: &Self, from: BoardPosition, roll: usize) -> Vec<Card> {
+        let mut rooms = self.positions_reachable(from, roll).into_iter().filter_map(|position| match position {
+            BoardPosition::Room(room) => Some(room),
+            BoardPosition::Square { .. } => None
+        }).collect::<Vec<Card>>();
+        if let BoardPosition::Room(room) = from {
+            if let Some(other_room) = self.secret_passage(room) {
+                if !rooms.contains(&other_room) {
+                    rooms.push(other_room);
+                }
+            }
+        }
+        rooms.sort();
+        return rooms;
+    }
+
+    // The chance of rolling each total, indexed by the total.
+    pub fn roll_probabilities(self: &Self) -> Vec<f64> {
+        let mut probabilities = vec![1.0];
+        for _ in 0..self.num_dice {
+            let mut next_probabilities = vec![0.0; probabilities.len() + 6];
+            for (total, probability) in probabilities.iter().enumerate() {
+                for face in 1..=6 {
+                    next_probabilities[total + face] += probability / 6.0;
+                }
+            }
+            probabilities = next_probabilities;
+        }
+        return probabilities;
+    }
+
+    // How many turns it takes on average to get into room, heading there as
+    // quickly as possible.  This is 0 if we're already there, and infinite
+    // if there's no way to get there.
+    pub fn expected_turns_to_reach(self: &Self, from: BoardPosition, room: Card) -> f64 {
+        return self.expected_turns_to_reach_from_everywhere(room)[self.index_of(from)];
+    }
+
+    // Like expected_turns_to_reach(), for every position in positions().
+    pub fn expected_turns_to_reach_from_everywhere(self: &Self, room: Card) -> Vec<f64> {
+        let target_index = self.index_of(BoardPosition::Room(room));
+        let roll_probabilities = self.roll_probabilities();
+        let max_roll = roll_probabilities.len() - 1;
+        let can_reach = (0..self.positions.len()).map(|index| {
+            // Either walking or taking a secret passage to somewhere that can walk there.
+            self.distances[index][target_index].is_some() || match self.positions[index] {
+                BoardPosition::Room(from_room) => self.secret_passage(from_room).is_some_and(|other_room| {
+                    self.distances[self.index_of(BoardPosition::Room(other_room))][target_index].is_some()
+                }),
+                BoardPosition::Square { .. } => false
+            }
+        }).collect::<Vec<bool>>();
+        // Each turn we either take a secret passage, or roll and then move to
+        // whichever reachable position is best.  Starting from 0 and
+        // repeating this only ever increases the estimates, and they
+        // converge to the real answer.
+        let mut expected_turns = vec![0.0; self.positions.len()];
+        for _ in 0..10000 {
+            let mut max_change: f64 = 0.0;
+            for index in 0..self.positions.len() {
+                if index == target_index || !can_reach[index] {
+                    continue;
+                }
+                // The best position exactly d steps away, and then at most
+                // d steps away.
+                let mut best_at_distance = vec![f64::INFINITY; max_roll + 1];
+                for (other_index, distance) in self.distances[index].iter().enumerate() {
+                    if let Some(distance) = distance {
+                        if *distance >= 1 && *distance <= max_roll && can_reach[other_index] {
+                            best_at_distance[*distance] = best_at_distance[*distance].min(expected_turns[other_index]);
+                        }
+                    }
+                }
+                let mut best_within = best_at_distance.clone();
+                for distance in 1..=max_roll {
+                    best_within[distance] = best_within[distance].min(best_within[distance - 1]);
+                }
+                let mut after_rolling = 0.0;
+                for (roll, probability) in roll_probabilities.iter().enumerate() {
+                    if *probability > 0.0 {
+                        after_rolling += probability * best_within[roll];
+                    }
+                }
+                let mut best = after_rolling;
+                if let BoardPosition::Room(from_room) = self.positions[index] {
+                    if let Some(other_room) = self.secret_passage(from_room) {
+                        best = best.min(expected_turns[self.index_of(BoardPosition::Room(other_room))]);
+                    }
+                }
+                let new_expected_turns = 1.0 + best;
+                max_change = max_change.max((new_expected_turns - expected_turns[index]).abs());
+                expected_turns[index] = new_expected_turns;
+            }
+            if max_change < 1e-9 {
+                break;
+            }
+        }
+        for index in 0..self.positions.len() {
+            if !can_reach[index] {
+                expected_turns[index] = f64::INFINITY;
+            }
+        }
+        return expected_turns;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::*;
+
+    #[test]
+    fn test_standard_layout_is_rectangular() {
+        assert!(STANDARD_LAYOUT.iter().all(|row| row.len() == 24));
+    }
+
+    #[test]
+    fn test_every_room_reachable_from_every_start() {
+        let board = Board::standard();
+        for (suspect, _, _) in STANDARD_START_SQUARES.iter() {
+            let start = board.start_position(*suspect).unwrap();
+            for room in CardUtils::cards_of_type(CardType::Room) {
+                assert!(board.distance(start, BoardPosition::Room(room)).is_some());
+                assert!(board.expected_turns_to_reach(start, room).is_finite());
+            }
+        }
+    }
+
+    #[test]
+    fn test_distances() {
+        let board = Board::standard();
+        assert_eq!(Some(7), board.distance(BoardPosition::Room(Card::Kitchen), BoardPosition::Room(Card::Ballroom)));
+        assert_eq!(Some(6), board.distance(BoardPosition::Square { row: 24, column: 7 }, BoardPosition::Room(Card::Lounge)));
+        // Can't walk through a room to get somewhere else
+        assert_eq!(Some(15), board.distance(BoardPosition::Square { row: 4, column: 7 }, BoardPosition::Square { row: 4, column: 16 }));
+    }
+
+    #[test]
+    fn test_rooms_reachable() {
+        let board = Board::standard();
+        // Only the secret passage
+        assert_eq!(vec![Card::Study], board.rooms_reachable(BoardPosition::Room(Card::Kitchen), 6));
+        assert_eq!(vec![Card::Study, Card::Ballroom], board.rooms_reachable(BoardPosition::Room(Card::Kitchen), 7));
+        assert_eq!(vec![Card::Lounge], board.rooms_reachable(BoardPosition::Square { row: 24, column: 7 }, 6));
+        assert!(board.rooms_reachable(BoardPosition::Square { row: 24, column: 7 }, 5).is_empty());
+    }
+
+    #[test]
+    fn test_roll_probabilities() {
+        let mut board = Board::standard();
+        assert_eq!(7, board.roll_probabilities().len());
+        board.num_dice = 2;
+        let probabilities = board.roll_probabilities();
+        assert_eq!(13, probabilities.len());
+        assert!((probabilities[7] - 6.0 / 36.0).abs() < 1e-12);
+        assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_expected_turns_to_reach() {
+        let board = Board::standard();
+        assert_eq!(0.0, board.expected_turns_to_reach(BoardPosition::Room(Card::Kitchen), Card::Kitchen));
+        assert_eq!(1.0, board.expected_turns_to_reach(BoardPosition::Room(Card::Kitchen), Card::Study));
+        let turns = board.expected_turns_to_reach(BoardPosition::Square { row: 24, column: 7 }, Card::Lounge);
+        // Only a 6 gets there in one turn
+        assert!(turns > 1.0 + 5.0 / 6.0);
+        assert!(turns > board.expected_turns_to_reach(BoardPosition::Square { row: 23, column: 7 }, Card::Lounge));
+    }
+}
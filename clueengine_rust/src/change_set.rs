@@ -0,0 +1,93 @@
+// Exactly what learning something changed, so callers don't have to compare
+// the engine before and after.  See ClueEngine::learn_info_on_card() and
+// friends.
+
+use crate::{Card, CardSet, ClauseChanges, Fact};
+
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct ChangeSet {
+    // Facts we didn't know before, in the order we learned them.
+    pub new_facts: Vec<Fact>,
+    // (player index, clause).  A clause that gets smaller shows up as the old
+    // clause removed and the new one added.  Clauses that were added and then
+    // removed again aren't in either.
+    pub clauses_added: Vec<(usize, CardSet)>,
+    pub clauses_removed: Vec<(usize, CardSet)>,
+    // Cards newly known to be in the solution.  These are also in new_facts.
+    pub solution_cards: Vec<Card>,
+}
+
+impl ChangeSet {
+    pub fn new() -> ChangeSet {
+        return ChangeSet::default();
+    }
+
+    pub fn is_empty(self: &Self) -> bool {
+        return self.new_facts.is_empty() && self.clauses_added.is_empty() && self.clauses_removed.is_empty();
+    }
+
+    // The cards we learned a new fact about.
+    pub fn cards(self: &Self) -> CardSet {
+        return self.new_facts.iter().map(|fact| fact.card).collect();
+    }
+
+    pub(crate) fn add_fact(self: &mut Self, fact: Fact, is_solution_player: bool) {
+        self.new_facts.push(fact);
+        if is_solution_player && fact.has_card {
+            self.solution_cards.push(fact.card);
+        }
+    }
+
+    // Records what a change to a player's clauses did, as returned by the
+    // ClauseStore.
+    pub(crate) fn add_clause_changes(self: &mut Self, player_index: usize, changes: ClauseChanges) {
+        for clause in changes.removed {
+            let entry = (player_index, clause);
+            match self.clauses_added.iter().position(|added| *added == entry) {
+                Some(position) => { self.clauses_added.remove(position); },
+                None => self.clauses_removed.push(entry)
+            }
+        }
+        for clause in changes.added {
+            let entry = (player_index, clause);
+            match self.clauses_removed.iter().position(|removed| *removed == entry) {
+                Some(position) => { self.clauses_removed.remove(position); },
+                None => self.clauses_added.push(entry)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::change_set::*;
+    use std::collections::HashSet;
+    use std::iter::FromIterator;
+
+    fn make_card_set(cards: Vec<Card>) -> CardSet {
+        return HashSet::from_iter(cards);
+    }
+
+    #[test]
+    fn test_clause_changes_cancel_out() {
+        let mut change_set = ChangeSet::new();
+        let big = make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Hall]);
+        let small = make_card_set(vec![Card::ProfessorPlum, Card::Knife]);
+        change_set.add_clause_changes(1, ClauseChanges { added: vec![big.clone()], removed: vec![] });
+        change_set.add_clause_changes(1, ClauseChanges { added: vec![small.clone()], removed: vec![big] });
+        assert_eq!(vec![(1, small.clone())], change_set.clauses_added);
+        assert!(change_set.clauses_removed.is_empty());
+        change_set.add_clause_changes(1, ClauseChanges { added: vec![], removed: vec![small] });
+        assert!(change_set.is_empty());
+    }
+
+    #[test]
+    fn test_solution_cards() {
+        let mut change_set = ChangeSet::new();
+        change_set.add_fact(Fact { player_index: 3, card: Card::Knife, has_card: true }, true);
+        change_set.add_fact(Fact { player_index: 3, card: Card::Rope, has_card: false }, true);
+        change_set.add_fact(Fact { player_index: 0, card: Card::Hall, has_card: true }, false);
+        assert_eq!(vec![Card::Knife], change_set.solution_cards);
+        assert_eq!(make_card_set(vec![Card::Knife, Card::Rope, Card::Hall]), change_set.cards());
+    }
+}
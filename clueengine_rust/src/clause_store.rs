@@ -0,0 +1,279 @@
+// A player's clauses (sets of cards the player has at least one of), kept
+// minimal as they change: no clause is a subset of another one, since then
+// the bigger clause doesn't tell us anything.
+//
+// Each clause is also stored as a mask of cards (see
+// CardUtils::card_set_to_mask()) so subset checks are cheap, and there's an
+// index from each card to the clauses it's in, so learning about a card finds
+// the clauses it changes without going through all of them.  (The deduction
+// rules still look at all of a player's clauses when anything about that
+// player changes, since whether a card is forced depends on every clause.)
+
+use crate::{Card, CardSet, CardUtils, CARD_LAST};
+use num_traits::FromPrimitive;
+use std::ops::Deref;
+
+// What a change to a ClauseStore did.  A clause that gets smaller shows up as
+// the old clause removed and the new one added.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClauseChanges {
+    pub added: Vec<CardSet>,
+    pub removed: Vec<CardSet>,
+}
+
+impl ClauseChanges {
+    pub fn is_empty(self: &Self) -> bool {
+        return self.added.is_empty() && self.removed.is_empty();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ClauseStore {
+    // In the order they were added
+    clauses: Vec<CardSet>,
+    masks: Vec<u32>,
+    // Indexed by card, the positions of the clauses with that card in them
+    clauses_with_card: Vec<Vec<usize>>,
+}
+
+impl ClauseStore {
+    pub fn new() -> ClauseStore {
+        return ClauseStore { clauses: vec![], masks: vec![], clauses_with_card: vec![vec![]; CARD_LAST as usize] };
+    }
+
+    // Adds a clause, unless an existing clause is a subset of it.  Any
+    // existing clauses that are supersets of it are removed.  If the clause
+    // wasn't added, nothing changes.
+    pub fn add(self: &mut Self, clause: CardSet) -> ClauseChanges {
+        let mask = CardUtils::card_set_to_mask(&clause);
+        if self.masks.iter().any(|existing_mask| existing_mask & !mask == 0) {
+            return ClauseChanges::default();
+        }
+        let supersets = self.masks.iter().map(|existing_mask| mask & !existing_mask == 0).collect::<Vec<bool>>();
+        let removed = if supersets.contains(&true) { self.remove_where(&supersets) } else { vec![] };
+        let position = self.clauses.len();
+        for card in clause.iter() {
+            self.clauses_with_card[*card as usize].push(position);
+        }
+        self.clauses.push(clause.clone());
+        self.masks.push(mask);
+        return ClauseChanges { added: vec![clause], removed };
+    }
+
+    // For when we learn the player has the card: any clause with it in it is
+    // satisfied.
+    pub fn remove_clauses_with_card(self: &mut Self, card: Card) -> ClauseChanges {
+        if self.clauses_with_card[card as usize].is_empty() {
+            return ClauseChanges::default();
+        }
+        let mut to_remove = vec![false; self.clauses.len()];
+        for position in self.clauses_with_card[card as usize].iter() {
+            to_remove[*position] = true;
+        }
+        return ClauseChanges { added: vec![], removed: self.remove_where(&to_remove) };
+    }
+
+    // For when we learn the player doesn't have the card: it can be taken out
+    // of every clause.  A clause with one card left tells us the player has
+    // that card, but that's up to the caller.
+    pub fn remove_card(self: &mut Self, card: Card) -> ClauseChanges {
+        let changed_positions = std::mem::take(&mut self.clauses_with_card[card as usize]);
+        if changed_positions.is_empty() {
+            return ClauseChanges::default();
+        }
+        let mut changes = ClauseChanges::default();
+        for position in changed_positions.iter() {
+            changes.removed.push(self.clauses[*position].clone());
+            self.clauses[*position].remove(&card);
+            self.masks[*position] &= !(1 << (card as u32));
+        }
+        // Only the clauses that got smaller can be a subset of another clause
+        // now.  If two clauses end up the same, keep the first one.
+        let mut to_remove = vec![false; self.clauses.len()];
+        for changed_position in changed_positions.iter().copied() {
+            let changed_mask = self.masks[changed_position];
+            for (other_position, other_mask) in self.masks.iter().enumerate() {
+                if other_position == changed_position || to_remove[other_position] || to_remove[changed_position] {
+                    continue;
+                }
+                if changed_mask == *other_mask {
+                    to_remove[changed_position.max(other_position)] = true;
+                }
+                else if changed_mask & !other_mask == 0 {
+                    to_remove[other_position] = true;
+                }
+                else if other_mask & !changed_mask == 0 {
+                    to_remove[changed_position] = true;
+                }
+            }
+        }
+        for changed_position in changed_positions.iter() {
+            if !to_remove[*changed_position] {
+                changes.added.push(self.clauses[*changed_position].clone());
+            }
+        }
+        for (position, clause) in self.clauses.iter().enumerate() {
+            if to_remove[position] && !changed_positions.contains(&position) {
+                changes.removed.push(clause.clone());
+            }
+        }
+        if to_remove.contains(&true) {
+            self.remove_where(&to_remove);
+        }
+        return changes;
+    }
+
+    // The clauses that have this card in them.
+    pub fn clauses_with_card(self: &Self, card: Card) -> impl Iterator<Item=&CardSet> {
+        return self.clauses_with_card[card as usize].iter().map(move |position| &self.clauses[*position]);
+    }
+
+    // The cards in clauses with only one card, which the player must have.
+    pub fn single_card_clauses(self: &Self) -> impl Iterator<Item=Card> + '_ {
+        return self.masks.iter().filter(|mask| mask.count_ones() == 1).map(|mask| FromPrimitive::from_u32(mask.trailing_zeros()).unwrap());
+    }
+
+    // The clauses as masks of cards, in the same order.
+    pub fn masks(self: &Self) -> &[u32] {
+        return &self.masks;
+    }
+
+    // Returns the clauses that were removed.
+    fn remove_where(self: &mut Self, to_remove: &[bool]) -> Vec<CardSet> {
+        let removed = self.clauses.iter().zip(to_remove.iter()).filter(|(_, remove)| **remove).map(|(clause, _)| clause.clone()).collect::<Vec<CardSet>>();
+        let mut position = 0;
+        self.clauses.retain(|_| { position += 1; !to_remove[position - 1] });
+        let mut position = 0;
+        self.masks.retain(|_| { position += 1; !to_remove[position - 1] });
+        // Positions have moved, so start the index over.
+        for positions in self.clauses_with_card.iter_mut() {
+            positions.clear();
+        }
+        for (position, clause) in self.clauses.iter().enumerate() {
+            for card in clause.iter() {
+                self.clauses_with_card[*card as usize].push(position);
+            }
+        }
+        return removed;
+    }
+}
+
+impl Default for ClauseStore {
+    fn default() -> ClauseStore {
+        return ClauseStore::new();
+    }
+}
+
+impl Deref for ClauseStore {
+    type Target = [CardSet];
+
+    fn deref(self: &Self) -> &[CardSet] {
+        return &self.clauses;
+    }
+}
+
+impl PartialEq for ClauseStore {
+    fn eq(self: &Self, other: &ClauseStore) -> bool {
+        return self.clauses == other.clauses;
+    }
+}
+
+impl PartialEq<Vec<CardSet>> for ClauseStore {
+    fn eq(self: &Self, other: &Vec<CardSet>) -> bool {
+        return &self.clauses == other;
+    }
+}
+
+impl PartialEq<ClauseStore> for Vec<CardSet> {
+    fn eq(self: &Self, other: &ClauseStore) -> bool {
+        return self == &other.clauses;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clause_store::*;
+    use std::collections::HashSet;
+    use std::iter::FromIterator;
+
+    fn make_card_set(cards: Vec<Card>) -> CardSet {
+        return HashSet::from_iter(cards);
+    }
+
+    fn check_index(store: &ClauseStore) {
+        for card in CardUtils::all_cards() {
+            let expected = store.iter().filter(|clause| clause.contains(&card)).collect::<Vec<&CardSet>>();
+            assert_eq!(expected, store.clauses_with_card(card).collect::<Vec<&CardSet>>());
+        }
+        assert_eq!(store.iter().map(CardUtils::card_set_to_mask).collect::<Vec<u32>>(), store.masks());
+    }
+
+    #[test]
+    fn test_add_rejects_superset() {
+        let mut store = ClauseStore::new();
+        assert_eq!(vec![make_card_set(vec![Card::ProfessorPlum, Card::Knife])], store.add(make_card_set(vec![Card::ProfessorPlum, Card::Knife])).added);
+        assert!(store.add(make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Hall])).is_empty());
+        assert!(store.add(make_card_set(vec![Card::ProfessorPlum, Card::Knife])).is_empty());
+        assert_eq!(vec![make_card_set(vec![Card::ProfessorPlum, Card::Knife])], store);
+        check_index(&store);
+    }
+
+    #[test]
+    fn test_add_removes_supersets() {
+        let mut store = ClauseStore::new();
+        store.add(make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Hall]));
+        store.add(make_card_set(vec![Card::MrGreen, Card::Rope, Card::Study]));
+        store.add(make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Lounge]));
+        let changes = store.add(make_card_set(vec![Card::ProfessorPlum, Card::Knife]));
+        assert_eq!(vec![make_card_set(vec![Card::ProfessorPlum, Card::Knife])], changes.added);
+        assert_eq!(vec![
+            make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Hall]),
+            make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Lounge])], changes.removed);
+        assert_eq!(vec![make_card_set(vec![Card::MrGreen, Card::Rope, Card::Study]), make_card_set(vec![Card::ProfessorPlum, Card::Knife])], store);
+        check_index(&store);
+    }
+
+    #[test]
+    fn test_remove_clauses_with_card() {
+        let mut store = ClauseStore::new();
+        store.add(make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Hall]));
+        store.add(make_card_set(vec![Card::MrGreen, Card::Rope, Card::Study]));
+        store.add(make_card_set(vec![Card::ProfessorPlum, Card::Wrench, Card::Lounge]));
+        let changes = store.remove_clauses_with_card(Card::ProfessorPlum);
+        assert!(changes.added.is_empty());
+        assert_eq!(vec![
+            make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Hall]),
+            make_card_set(vec![Card::ProfessorPlum, Card::Wrench, Card::Lounge])], changes.removed);
+        assert_eq!(vec![make_card_set(vec![Card::MrGreen, Card::Rope, Card::Study])], store);
+        check_index(&store);
+    }
+
+    #[test]
+    fn test_remove_card() {
+        let mut store = ClauseStore::new();
+        store.add(make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Hall]));
+        store.add(make_card_set(vec![Card::Knife, Card::Hall, Card::Lounge]));
+        store.add(make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Study]));
+        store.add(make_card_set(vec![Card::MrGreen, Card::Rope]));
+        let changes = store.remove_card(Card::ProfessorPlum);
+        assert_eq!(vec![make_card_set(vec![Card::Knife, Card::Hall]), make_card_set(vec![Card::Knife, Card::Study])], changes.added);
+        assert_eq!(vec![
+            make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Hall]),
+            make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Study]),
+            make_card_set(vec![Card::Knife, Card::Hall, Card::Lounge])], changes.removed);
+        // Knife, Hall is a subset of the second clause, and Knife, Study isn't
+        assert_eq!(vec![
+            make_card_set(vec![Card::Knife, Card::Hall]),
+            make_card_set(vec![Card::Knife, Card::Study]),
+            make_card_set(vec![Card::MrGreen, Card::Rope])], store);
+        check_index(&store);
+
+        let changes = store.remove_card(Card::Hall);
+        assert_eq!(vec![make_card_set(vec![Card::Knife])], changes.added);
+        assert_eq!(vec![make_card_set(vec![Card::Knife, Card::Hall]), make_card_set(vec![Card::Knife, Card::Study])], changes.removed);
+        assert!(store.remove_card(Card::Study).is_empty());
+        assert_eq!(vec![make_card_set(vec![Card::Knife]), make_card_set(vec![Card::MrGreen, Card::Rope])], store);
+        check_index(&store);
+        assert_eq!(vec![Card::Knife], store.single_card_clauses().collect::<Vec<Card>>());
+    }
+}
@@ -0,0 +1,302 @@
+// Encodes everything a ClueEngine knows as a satisfiability problem in
+// conjunctive normal form.  There's one variable for each (player, card) pair
+// that's true if the player has the card, plus extra variables used to count
+// how many cards each player has.
+
+use crate::{Card, CardSet, CardUtils, ClueEngine, Deal, CARD_LAST};
+use crate::sat::SatSolver;
+use std::cmp::min;
+
+#[derive(Debug, Clone)]
+pub struct ClueCnf {
+    // Includes the solution player
+    pub num_players: usize,
+    pub num_variables: usize,
+    // Each clause is a list of literals, using the DIMACS convention.
+    pub clauses: Vec<Vec<i32>>,
+}
+
+// Used while building clauses so that counter variables that are always true
+// or always false don't need a variable.
+#[derive(Debug, Copy, Clone)]
+enum Term {
+    True,
+    False,
+    Literal(i32)
+}
+
+impl Term {
+    fn negate(self: Self) -> Term {
+        return match self {
+            Term::True => Term::False,
+            Term::False => Term::True,
+            Term::Literal(literal) => Term::Literal(-literal)
+        };
+    }
+}
+
+impl ClueCnf {
+    pub fn from_engine(engine: &ClueEngine) -> ClueCnf {
+        let num_players = engine.player_data.len();
+        let mut cnf = ClueCnf { num_players, num_variables: num_players * CARD_LAST as usize, clauses: vec![] };
+        for (player_index, player) in engine.player_data.iter().enumerate() {
+            for card in player.has_cards.iter() {
+                cnf.clauses.push(vec![cnf.ownership_variable(player_index, *card)]);
+            }
+            for card in player.not_has_cards.iter() {
+                cnf.clauses.push(vec![-cnf.ownership_variable(player_index, *card)]);
+            }
+            for clause in player.possible_cards.iter() {
+                let mut literals = clause.iter().map(|card| cnf.ownership_variable(player_index, *card)).collect::<Vec<i32>>();
+                literals.sort_unstable();
+                cnf.clauses.push(literals);
+            }
+            if let Some(num_cards) = player.num_cards {
+                let literals = CardUtils::all_cards().map(|card| cnf.ownership_variable(player_index, card)).collect::<Vec<i32>>();
+                cnf.add_exactly(&literals, num_cards as usize);
+            }
+        }
+        // Every card is somewhere, and only in one place.
+        for card in CardUtils::all_cards() {
+            let literals = (0..num_players).map(|player_index| cnf.ownership_variable(player_index, card)).collect::<Vec<i32>>();
+            cnf.add_exactly(&literals, 1);
+        }
+        // The solution has exactly one card of each type.
+        let solution_player_index = engine.number_of_real_players();
+        for card_type in CardUtils::all_card_types() {
+            let literals = CardUtils::cards_of_type(*card_type).map(|card| cnf.ownership_variable(solution_player_index, card)).collect::<Vec<i32>>();
+            cnf.add_exactly(&literals, 1);
+        }
+        return cnf;
+    }
+
+    // The variable that's true if the player has the card.  These are always
+    // the first variables, so they're numbered 1 through
+    // num_players * CARD_LAST.
+    pub fn ownership_variable(self: &Self, player_index: usize, card: Card) -> i32 {
+        return (player_index * CARD_LAST as usize + card as usize + 1) as i32;
+    }
+
+    // Writes the clauses in DIMACS CNF format, so they can be given to other
+    // SAT solvers.  The comments at the top say what the ownership variables
+    // mean: "c var <variable> <player index> <card>", where the card is the
+    // same letter used in ClueEngine::write_to_string() and the last player
+    // is the solution.
+    pub fn write_to_dimacs(self: &Self) -> String {
+        let mut s = String::from("c Clue game constraints\n");
+        for player_index in 0..self.num_players {
+            for card in CardUtils::all_cards() {
+                s += &format!("c var {} {} {}\n", self.ownership_variable(player_index, card), player_index, CardUtils::char_from_card(card));
+            }
+        }
+        s += &format!("p cnf {} {}\n", self.num_variables, self.clauses.len());
+        for clause in self.clauses.iter() {
+            for literal in clause.iter() {
+                s += &literal.to_string();
+                s += " ";
+            }
+            s += "0\n";
+        }
+        return s;
+    }
+
+    // Reads a satisfying assignment in the format SAT solvers usually print
+    // it: an optional "s SATISFIABLE" line, then "v" lines with the literals
+    // that are true.  Lines starting with "c" are ignored, and so is the "v"
+    // at the start of a line, so a plain list of literals works too.
+    // Variables that aren't mentioned are false.
+    pub fn parse_model(self: &Self, s: &str) -> Result<Vec<bool>, String> {
+        let mut model = vec![false; self.num_variables + 1];
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('c') {
+                continue;
+            }
+            if line.starts_with('s') {
+                if line != "s SATISFIABLE" {
+                    return Err(format!("Not a satisfying assignment: '{}'", line));
+                }
+                continue;
+            }
+            let literals = line.strip_prefix('v').unwrap_or(line);
+            for literal in literals.split_whitespace() {
+                let literal = literal.parse::<i32>().map_err(|_| format!("Invalid literal '{}'", literal))?;
+                let variable = literal.unsigned_abs() as usize;
+                if variable > self.num_variables {
+                    return Err(format!("Variable {} is out of range (there are {} variables)", variable, self.num_variables));
+                }
+                // 0 just ends a list of literals
+                if variable != 0 {
+                    model[variable] = literal > 0;
+                }
+            }
+        }
+        return Ok(model);
+    }
+
+    // Returns the deal a satisfying assignment describes, after checking
+    // that it does satisfy all the clauses.
+    pub fn deal_from_model(self: &Self, model: &[bool]) -> Result<Deal, String> {
+        if model.len() != self.num_variables + 1 {
+            return Err(format!("Expected values for {} variables, got {}", self.num_variables, model.len() as isize - 1));
+        }
+        if let Some(clause) = self.clauses.iter().find(|clause| !clause.iter().any(|literal| model[literal.unsigned_abs() as usize] == (*literal > 0))) {
+            return Err(format!("Clause {:?} isn't satisfied", clause));
+        }
+        let hands = (0..self.num_players).map(|player_index| {
+            CardUtils::all_cards().filter(|card| model[self.ownership_variable(player_index, *card) as usize]).collect::<CardSet>()
+        }).collect();
+        return Ok(Deal { hands });
+    }
+
+    // Turns a satisfying assignment into an engine where we know who has
+    // every card.
+    pub fn engine_from_model(self: &Self, model: &[bool]) -> Result<ClueEngine, String> {
+        return ClueEngine::from_deal(&self.deal_from_model(model)?);
+    }
+
+    pub fn to_solver(self: &Self) -> SatSolver {
+        let mut solver = SatSolver::new(self.num_variables);
+        for clause in self.clauses.iter() {
+            solver.add_clause(clause);
+        }
+        return solver;
+    }
+
+    fn new_variable(self: &mut Self) -> i32 {
+        self.num_variables += 1;
+        return self.num_variables as i32;
+    }
+
+    fn add_terms(self: &mut Self, terms: &[Term]) {
+        let mut clause = vec![];
+        for term in terms {
+            match term {
+                Term::True => return,
+                Term::False => {},
+                Term::Literal(literal) => clause.push(*literal)
+            }
+        }
+        self.clauses.push(clause);
+    }
+
+    // Adds clauses saying exactly count of the literals are true, using a
+    // sequential counter: at_least[i][j] is true exactly when at least j of
+    // the first i literals are true.  We only need to count up to count + 1.
+    fn add_exactly(self: &mut Self, literals: &[i32], count: usize) {
+        let max_count = count + 1;
+        let mut at_least: Vec<Vec<i32>> = vec![vec![]];
+        for i in 1..=literals.len() {
+            let row = (1..=min(i, max_count)).map(|_| self.new_variable()).collect::<Vec<i32>>();
+            at_least.push(row);
+        }
+        let term = |i: usize, j: usize| -> Term {
+            if j == 0 {
+                return Term::True;
+            }
+            if j > i || j > max_count {
+                return Term::False;
+            }
+            return Term::Literal(at_least[i][j - 1]);
+        };
+        let mut new_clauses = vec![];
+        for i in 1..=literals.len() {
+            let literal = Term::Literal(literals[i - 1]);
+            for j in 1..=min(i, max_count) {
+                let current = term(i, j);
+                // If at least j of the first i - 1 are true, or the ith is
+                // true and at least j - 1 of the first i - 1 are, then at
+                // least j of the first i are true.
+                new_clauses.push(vec![term(i - 1, j).negate(), current]);
+                new_clauses.push(vec![literal.negate(), term(i - 1, j - 1).negate(), current]);
+                // And the other way around.
+                new_clauses.push(vec![current.negate(), term(i - 1, j), literal]);
+                new_clauses.push(vec![current.negate(), term(i - 1, j), term(i - 1, j - 1)]);
+            }
+        }
+        new_clauses.push(vec![term(literals.len(), count)]);
+        new_clauses.push(vec![term(literals.len(), max_count).negate()]);
+        for clause in new_clauses {
+            self.add_terms(&clause);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cnf::*;
+
+    fn count_models(cnf: &ClueCnf, literals: &[i32]) -> Vec<usize> {
+        // Returns how many of the literals are true in each model of cnf,
+        // found by blocking each model we find.
+        let mut counts = vec![];
+        let mut cnf = cnf.clone();
+        loop {
+            match cnf.to_solver().solve(&[]) {
+                None => return counts,
+                Some(model) => {
+                    counts.push(literals.iter().filter(|literal| model[**literal as usize]).count());
+                    cnf.clauses.push(literals.iter().map(|literal| if model[*literal as usize] { -literal } else { *literal }).collect());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_to_dimacs() {
+        let engine = ClueEngine::load_from_string("63A-.3-.3-.3-.3-.3-.3-.").unwrap();
+        let cnf = ClueCnf::from_engine(&engine);
+        let dimacs = cnf.write_to_dimacs();
+        let lines = dimacs.lines().collect::<Vec<&str>>();
+        assert!(lines.contains(&"c var 1 0 A"));
+        assert!(lines.contains(&"c var 43 2 A"));
+        assert!(lines.contains(&format!("p cnf {} {}", cnf.num_variables, cnf.clauses.len()).as_str()));
+        // Player 0 has ProfessorPlum
+        assert!(lines.contains(&"1 0"));
+        assert_eq!(cnf.clauses.len(), lines.iter().filter(|line| line.ends_with(" 0")).count());
+    }
+
+    #[test]
+    fn test_parse_model() {
+        let cnf = ClueCnf { num_players: 0, num_variables: 4, clauses: vec![] };
+        assert_eq!(vec![false, true, false, true, false], cnf.parse_model("c comment\ns SATISFIABLE\nv 1 -2\nv 3 -4 0\n").unwrap());
+        assert_eq!(vec![false, false, true, false, false], cnf.parse_model("-1 2 -3 -4 0").unwrap());
+        assert!(cnf.parse_model("s UNSATISFIABLE").is_err());
+        assert!(cnf.parse_model("v 1 5 0").is_err());
+        assert!(cnf.parse_model("v 1 x 0").is_err());
+    }
+
+    #[test]
+    fn test_engine_from_model() {
+        let mut engine = ClueEngine::new(3, None).unwrap();
+        engine.learn_suggest(0, Card::ProfessorPlum, Card::Knife, Card::Hall, Some(1), None);
+        engine.learn_info_on_card(2, Card::Rope, true, true);
+        let cnf = ClueCnf::from_engine(&engine);
+        let model = cnf.to_solver().solve(&[]).unwrap();
+
+        let dealt_engine = cnf.engine_from_model(&model).unwrap();
+
+        assert_eq!(Some(true), dealt_engine.player_data[2].has_card(Card::Rope));
+        assert!(engine.player_data[1].possible_cards[0].iter().any(|card| dealt_engine.player_data[1].has_card(*card) == Some(true)));
+        for player_index in 0..4 {
+            assert_eq!(engine.player_data[player_index].num_cards, dealt_engine.player_data[player_index].num_cards);
+            assert_eq!(21, dealt_engine.player_data[player_index].has_cards.len() + dealt_engine.player_data[player_index].not_has_cards.len());
+        }
+        let mut wrong_model = model.clone();
+        wrong_model[cnf.ownership_variable(2, Card::Rope) as usize] = false;
+        assert!(cnf.engine_from_model(&wrong_model).is_err());
+    }
+
+    #[test]
+    fn test_add_exactly() {
+        for count in 0..=5 {
+            let mut cnf = ClueCnf { num_players: 0, num_variables: 4, clauses: vec![] };
+            cnf.add_exactly(&[1, 2, 3, 4], count);
+            let counts = count_models(&cnf, &[1, 2, 3, 4]);
+            // There are (4 choose count) ways to pick which are true.
+            let expected = [1, 4, 6, 4, 1, 0][count];
+            assert_eq!(expected, counts.len());
+            assert!(counts.iter().all(|c| *c == count));
+        }
+    }
+}
@@ -0,0 +1,606 @@
+use std::collections::HashMap;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{Card, CardSet, CardType, CardUtils, ClueEngine, Deal};
+
+// Counts exactly how many deals are consistent with what a ClueEngine knows,
+// and samples uniformly from them.
+//
+// For each possible solution we count the ways to deal out the rest of the
+// cards.  Cards that show up in a clause are placed one at a time, keeping
+// track of which clauses are satisfied.  The rest of the cards only differ
+// in who is allowed to have them, so cards with the same set of possible
+// owners are dealt out as a group, counting the ways with binomials.
+// Sampling walks the same steps, picking each choice in proportion to the
+// number of deals it leads to.
+#[derive(Clone, Debug)]
+pub struct DealCounter {
+    num_players: usize,
+    solutions: Vec<SolutionCounter>,
+    total_count: u128,
+}
+
+impl DealCounter {
+    // The counts are kept in a bitmask, so we can't handle more clauses than this.
+    pub const MAX_CLAUSES: usize = 128;
+
+    pub fn new(engine: &ClueEngine) -> Result<DealCounter, String> {
+        let num_players = engine.number_of_real_players();
+        let mut hand_sizes = vec![];
+        for player in engine.player_data.iter() {
+            hand_sizes.push(player.num_cards.ok_or(String::from("Can't count deals without knowing how many cards everyone has"))?);
+        }
+        // Clauses the solution has must be satisfied by the solution itself,
+        // so only the real players' clauses are tracked while dealing.
+        let mut clauses: Vec<(usize, CardSet)> = vec![];
+        for player_index in 0..num_players {
+            for clause in engine.player_data[player_index].possible_cards.iter() {
+                clauses.push((player_index, clause.clone()));
+            }
+        }
+        if clauses.len() > Self::MAX_CLAUSES {
+            return Err(format!("Too many clauses to count deals ({}, maximum is {})", clauses.len(), Self::MAX_CLAUSES));
+        }
+
+        let solution_possibilities = engine.solution_possibilities();
+        let mut solutions = vec![];
+        let mut total_count: u128 = 0;
+        for card1 in solution_possibilities.get(&CardType::Suspect).unwrap() {
+            for card2 in solution_possibilities.get(&CardType::Weapon).unwrap() {
+                for card3 in solution_possibilities.get(&CardType::Room).unwrap() {
+                    let solution = [*card1, *card2, *card3];
+                    if let Some(mut counter) = SolutionCounter::new(engine, solution, &hand_sizes, &clauses) {
+                        counter.count = counter.count_all();
+                        if counter.count > 0 {
+                            total_count += counter.count;
+                            solutions.push(counter);
+                        }
+                    }
+                }
+            }
+        }
+        // Keep these in a predictable order so sampling with a seeded rng is repeatable.
+        solutions.sort_by_key(|counter| counter.solution);
+        Ok(DealCounter { num_players, solutions, total_count })
+    }
+
+    // The number of consistent deals.  Zero means what we know is inconsistent.
+    pub fn total_count(self: &DealCounter) -> u128 {
+        return self.total_count;
+    }
+
+    // The number of consistent deals for each possible solution (suspect, weapon, room).
+    // Solutions with no consistent deals are left out.
+    pub fn solution_counts(self: &DealCounter) -> Vec<([Card; 3], u128)> {
+        return self.solutions.iter().map(|counter| (counter.solution, counter.count)).collect();
+    }
+
+    // Calls f on every consistent deal (with one hand per player and the
+    // solution last).  There can be a lot of these, so check total_count() first!
+    pub fn for_each_deal<F: FnMut(&[CardSet])>(self: &mut DealCounter, mut f: F) {
+        for counter in self.solutions.iter_mut() {
+            counter.for_each_deal(self.num_players, &mut f);
+        }
+    }
+
+    // Picks a deal uniformly at random from all the consistent deals.
+    // Returns None if there aren't any.
+    pub fn sample<R: Rng + ?Sized>(self: &mut DealCounter, rng: &mut R) -> Option<Deal> {
+        if self.total_count == 0 {
+            return None;
+        }
+        let mut choice = rng.gen_range(0, self.total_count);
+        for counter in self.solutions.iter_mut() {
+            if choice < counter.count {
+                return Some(counter.sample(self.num_players, rng));
+            }
+            choice -= counter.count;
+        }
+        unreachable!("choice was less than the total count");
+    }
+}
+
+const MAX_HAND_SIZE_BITS: usize = 5;
+const HAND_SIZE_MASK: u64 = (1 << MAX_HAND_SIZE_BITS) - 1;
+
+// Number of cards each player still needs, packed MAX_HAND_SIZE_BITS bits per player.
+type Capacities = u64;
+// One bit per clause, set if the clause is satisfied.
+type ClauseMask = u128;
+
+fn capacity(capacities: Capacities, player_index: usize) -> usize {
+    return ((capacities >> (player_index * MAX_HAND_SIZE_BITS)) & HAND_SIZE_MASK) as usize;
+}
+
+fn remove_capacity(capacities: Capacities, player_index: usize, number: usize) -> Capacities {
+    return capacities - ((number as u64) << (player_index * MAX_HAND_SIZE_BITS));
+}
+
+fn binomial(n: usize, k: usize) -> u128 {
+    let mut result: u128 = 1;
+    for i in 0..k {
+        result = result * (n - i) as u128 / (i + 1) as u128;
+    }
+    return result;
+}
+
+// A card that has to be placed by itself because it's in a clause.
+#[derive(Clone, Debug)]
+struct ClauseCard {
+    card: Card,
+    // One bit per player that could have this card
+    possible_owners: u16,
+    // Which clauses are satisfied if each player gets this card
+    satisfies: Vec<ClauseMask>,
+}
+
+// Cards that aren't in any clauses and have the same possible owners.
+#[derive(Clone, Debug)]
+struct CardGroup {
+    cards: Vec<Card>,
+    possible_owners: Vec<usize>,
+}
+
+// How to deal out one CardGroup: how many cards each of its possible owners gets.
+struct GroupSplit {
+    counts: Vec<usize>,
+    capacities_after: Capacities,
+    ways: u128,
+}
+
+// Where we are in handing out the cards of groups[index] for one GroupSplit.
+struct SplitAssignment {
+    index: usize,
+    group: CardGroup,
+    counts_left: Vec<usize>,
+    capacities_after: Capacities,
+}
+
+#[derive(Clone, Debug)]
+struct SolutionCounter {
+    solution: [Card; 3],
+    clause_cards: Vec<ClauseCard>,
+    // required_clauses[i] is the clauses whose cards are all in clause_cards[..i],
+    // so they must be satisfied by then.
+    required_clauses: Vec<ClauseMask>,
+    groups: Vec<CardGroup>,
+    // cards_left_at_group[i] is the number of cards in groups[i..]
+    cards_left_at_group: Vec<usize>,
+    start_capacities: Capacities,
+    count: u128,
+    clause_memo: HashMap<(usize, Capacities, ClauseMask), u128>,
+    group_memo: HashMap<(usize, Capacities), u128>,
+}
+
+impl SolutionCounter {
+    // Returns None if this solution can't be right.
+    fn new(engine: &ClueEngine, solution: [Card; 3], hand_sizes: &[u8], clauses: &[(usize, CardSet)]) -> Option<SolutionCounter> {
+        let num_players = engine.number_of_real_players();
+        let solution_player = engine.solution_player();
+        if solution.iter().any(|card| solution_player.not_has_cards.contains(card)) {
+            return None;
+        }
+        if solution_player.has_cards.iter().any(|card| !solution.contains(card)) {
+            return None;
+        }
+        if !solution_player.possible_cards.iter().all(|clause| solution.iter().any(|card| clause.contains(card))) {
+            return None;
+        }
+        let mut start_capacities: Capacities = 0;
+        for (player_index, &hand_size) in hand_sizes.iter().take(num_players).enumerate() {
+            if hand_size as u64 > HAND_SIZE_MASK {
+                return None;
+            }
+            start_capacities |= (hand_size as u64) << (player_index * MAX_HAND_SIZE_BITS);
+        }
+
+        let mut clause_cards = vec![];
+        let mut groups: Vec<CardGroup> = vec![];
+        for card in CardUtils::all_cards() {
+            let mut possible_owners: u16 = 0;
+            let known_owners = (0..num_players).filter(|&i| engine.player_data[i].has_cards.contains(&card)).collect::<Vec<usize>>();
+            if solution.contains(&card) {
+                if !known_owners.is_empty() {
+                    return None;
+                }
+                continue;
+            }
+            if known_owners.len() > 1 {
+                return None;
+            }
+            for player_index in 0..num_players {
+                let player = &engine.player_data[player_index];
+                let allowed = if known_owners.is_empty() { !player.not_has_cards.contains(&card) } else { known_owners[0] == player_index };
+                if allowed {
+                    possible_owners |= 1 << player_index;
+                }
+            }
+            if possible_owners == 0 {
+                return None;
+            }
+            if clauses.iter().any(|(_, clause)| clause.contains(&card)) {
+                let satisfies = (0..num_players).map(|player_index| {
+                    let mut mask: ClauseMask = 0;
+                    for (clause_index, (clause_player, clause)) in clauses.iter().enumerate() {
+                        if *clause_player == player_index && clause.contains(&card) {
+                            mask |= 1 << clause_index;
+                        }
+                    }
+                    mask
+                }).collect();
+                clause_cards.push(ClauseCard { card, possible_owners, satisfies });
+            }
+            else {
+                let owners = (0..num_players).filter(|i| possible_owners & (1 << i) != 0).collect::<Vec<usize>>();
+                match groups.iter_mut().find(|group| group.possible_owners == owners) {
+                    Some(group) => group.cards.push(card),
+                    None => groups.push(CardGroup { cards: vec![card], possible_owners: owners }),
+                }
+            }
+        }
+
+        let mut required_clauses = vec![0; clause_cards.len() + 1];
+        for (clause_index, (_, clause)) in clauses.iter().enumerate() {
+            // Clause cards are in card order, so find the position of the last one.
+            let last_position = clause_cards.iter().rposition(|clause_card| clause.contains(&clause_card.card));
+            let first_required = last_position.map(|position| position + 1).unwrap_or(0);
+            for required in required_clauses.iter_mut().skip(first_required) {
+                *required |= 1 << clause_index;
+            }
+        }
+        let mut cards_left_at_group = vec![0; groups.len() + 1];
+        for i in (0..groups.len()).rev() {
+            cards_left_at_group[i] = cards_left_at_group[i + 1] + groups[i].cards.len();
+        }
+
+        Some(SolutionCounter {
+            solution,
+            clause_cards,
+            required_clauses,
+            groups,
+            cards_left_at_group,
+            start_capacities,
+            count: 0,
+            clause_memo: HashMap::new(),
+            group_memo: HashMap::new(),
+        })
+    }
+
+    fn count_all(self: &mut SolutionCounter) -> u128 {
+        return self.count_from_clause_card(0, self.start_capacities, 0);
+    }
+
+    fn total_capacity(capacities: Capacities) -> usize {
+        let mut total = 0;
+        let mut remaining = capacities;
+        while remaining != 0 {
+            total += (remaining & HAND_SIZE_MASK) as usize;
+            remaining >>= MAX_HAND_SIZE_BITS;
+        }
+        return total;
+    }
+
+    fn count_from_clause_card(self: &mut SolutionCounter, index: usize, capacities: Capacities, satisfied: ClauseMask) -> u128 {
+        let required = self.required_clauses[index];
+        if satisfied & required != required {
+            return 0;
+        }
+        if Self::total_capacity(capacities) != self.clause_cards.len() - index + self.cards_left_at_group[0] {
+            return 0;
+        }
+        if index == self.clause_cards.len() {
+            return self.count_from_group(0, capacities);
+        }
+        if let Some(count) = self.clause_memo.get(&(index, capacities, satisfied)) {
+            return *count;
+        }
+        let mut count = 0;
+        for player_index in 0..self.clause_cards[index].satisfies.len() {
+            if self.clause_cards[index].possible_owners & (1 << player_index) != 0 && capacity(capacities, player_index) > 0 {
+                let newly_satisfied = self.clause_cards[index].satisfies[player_index];
+                count += self.count_from_clause_card(index + 1, remove_capacity(capacities, player_index, 1), satisfied | newly_satisfied);
+            }
+        }
+        self.clause_memo.insert((index, capacities, satisfied), count);
+        return count;
+    }
+
+    fn count_from_group(self: &mut SolutionCounter, index: usize, capacities: Capacities) -> u128 {
+        if Self::total_capacity(capacities) != self.cards_left_at_group[index] {
+            return 0;
+        }
+        if index == self.groups.len() {
+            return 1;
+        }
+        if let Some(count) = self.group_memo.get(&(index, capacities)) {
+            return *count;
+        }
+        let mut count = 0;
+        for split in self.group_splits(index, capacities) {
+            count += split.ways * self.count_from_group(index + 1, split.capacities_after);
+        }
+        self.group_memo.insert((index, capacities), count);
+        return count;
+    }
+
+    // All the ways to split up a group's cards among its possible owners.
+    fn group_splits(self: &SolutionCounter, index: usize, capacities: Capacities) -> Vec<GroupSplit> {
+        let group = &self.groups[index];
+        let mut splits = vec![];
+        let mut counts = vec![0; group.possible_owners.len()];
+        Self::add_group_splits(group, 0, group.cards.len(), capacities, 1, &mut counts, &mut splits);
+        return splits;
+    }
+
+    fn add_group_splits(group: &CardGroup, owner_index: usize, cards_left: usize, capacities: Capacities, ways: u128, counts: &mut Vec<usize>, splits: &mut Vec<GroupSplit>) {
+        if owner_index == group.possible_owners.len() {
+            if cards_left == 0 {
+                splits.push(GroupSplit { counts: counts.clone(), capacities_after: capacities, ways });
+            }
+            return;
+        }
+        let player_index = group.possible_owners[owner_index];
+        let max_count = capacity(capacities, player_index).min(cards_left);
+        for count in 0..=max_count {
+            counts[owner_index] = count;
+            Self::add_group_splits(group, owner_index + 1, cards_left - count, remove_capacity(capacities, player_index, count), ways * binomial(cards_left, count), counts, splits);
+        }
+        counts[owner_index] = 0;
+    }
+
+    fn sample<R: Rng + ?Sized>(self: &mut SolutionCounter, num_players: usize, rng: &mut R) -> Deal {
+        let mut hands: Vec<CardSet> = vec![CardSet::new(); num_players + 1];
+        hands[num_players].extend(self.solution.iter());
+        let mut capacities = self.start_capacities;
+        let mut satisfied: ClauseMask = 0;
+        for index in 0..self.clause_cards.len() {
+            let mut options = vec![];
+            for player_index in 0..num_players {
+                if self.clause_cards[index].possible_owners & (1 << player_index) != 0 && capacity(capacities, player_index) > 0 {
+                    let next_capacities = remove_capacity(capacities, player_index, 1);
+                    let next_satisfied = satisfied | self.clause_cards[index].satisfies[player_index];
+                    let count = self.count_from_clause_card(index + 1, next_capacities, next_satisfied);
+                    options.push((player_index, next_capacities, next_satisfied, count));
+                }
+            }
+            let (player_index, next_capacities, next_satisfied, _) = *Self::choose_weighted(&options, |option| option.3, rng);
+            hands[player_index].insert(self.clause_cards[index].card);
+            capacities = next_capacities;
+            satisfied = next_satisfied;
+        }
+        for index in 0..self.groups.len() {
+            let mut options = vec![];
+            for split in self.group_splits(index, capacities) {
+                let weight = split.ways * self.count_from_group(index + 1, split.capacities_after);
+                options.push((split, weight));
+            }
+            let (split, _) = Self::choose_weighted(&options, |option| option.1, rng);
+            // Every way of handing out these cards is equally likely.
+            let group = &self.groups[index];
+            let mut cards = group.cards.clone();
+            cards.shuffle(rng);
+            let mut next_card = 0;
+            for (owner_index, &count) in split.counts.iter().enumerate() {
+                hands[group.possible_owners[owner_index]].extend(cards[next_card..(next_card + count)].iter());
+                next_card += count;
+            }
+            capacities = split.capacities_after;
+        }
+        return Deal { hands };
+    }
+
+    fn for_each_deal(self: &mut SolutionCounter, num_players: usize, f: &mut dyn FnMut(&[CardSet])) {
+        let mut hands: Vec<CardSet> = vec![CardSet::new(); num_players + 1];
+        hands[num_players].extend(self.solution.iter());
+        self.for_each_deal_from_clause_card(0, self.start_capacities, 0, &mut hands, f);
+    }
+
+    fn for_each_deal_from_clause_card(self: &mut SolutionCounter, index: usize, capacities: Capacities, satisfied: ClauseMask, hands: &mut Vec<CardSet>, f: &mut dyn FnMut(&[CardSet])) {
+        if index == self.clause_cards.len() {
+            self.for_each_deal_from_group(0, capacities, hands, f);
+            return;
+        }
+        let card = self.clause_cards[index].card;
+        for player_index in 0..self.clause_cards[index].satisfies.len() {
+            if self.clause_cards[index].possible_owners & (1 << player_index) != 0 && capacity(capacities, player_index) > 0 {
+                let next_capacities = remove_capacity(capacities, player_index, 1);
+                let next_satisfied = satisfied | self.clause_cards[index].satisfies[player_index];
+                // Don't bother going down paths that don't lead anywhere
+                if self.count_from_clause_card(index + 1, next_capacities, next_satisfied) > 0 {
+                    hands[player_index].insert(card);
+                    self.for_each_deal_from_clause_card(index + 1, next_capacities, next_satisfied, hands, f);
+                    hands[player_index].remove(&card);
+                }
+            }
+        }
+    }
+
+    fn for_each_deal_from_group(self: &mut SolutionCounter, index: usize, capacities: Capacities, hands: &mut Vec<CardSet>, f: &mut dyn FnMut(&[CardSet])) {
+        if index == self.groups.len() {
+            f(hands);
+            return;
+        }
+        for split in self.group_splits(index, capacities) {
+            if self.count_from_group(index + 1, split.capacities_after) > 0 {
+                let mut assignment = SplitAssignment { index, group: self.groups[index].clone(), counts_left: split.counts, capacities_after: split.capacities_after };
+                self.for_each_split_assignment(&mut assignment, 0, hands, f);
+            }
+        }
+    }
+
+    // Hands out the group's cards one at a time to each owner that still has
+    // room in this split, which gives every way of splitting them up once.
+    fn for_each_split_assignment(self: &mut SolutionCounter, assignment: &mut SplitAssignment, card_index: usize, hands: &mut Vec<CardSet>, f: &mut dyn FnMut(&[CardSet])) {
+        if card_index == assignment.group.cards.len() {
+            self.for_each_deal_from_group(assignment.index + 1, assignment.capacities_after, hands, f);
+            return;
+        }
+        let card = assignment.group.cards[card_index];
+        for owner_index in 0..assignment.group.possible_owners.len() {
+            if assignment.counts_left[owner_index] > 0 {
+                let player_index = assignment.group.possible_owners[owner_index];
+                assignment.counts_left[owner_index] -= 1;
+                hands[player_index].insert(card);
+                self.for_each_split_assignment(assignment, card_index + 1, hands, f);
+                hands[player_index].remove(&card);
+                assignment.counts_left[owner_index] += 1;
+            }
+        }
+    }
+
+    fn choose_weighted<'a, T, F: Fn(&T) -> u128, R: Rng + ?Sized>(options: &'a [T], weight: F, rng: &mut R) -> &'a T {
+        let total: u128 = options.iter().map(&weight).sum();
+        let mut choice = rng.gen_range(0, total);
+        for option in options.iter() {
+            if choice < weight(option) {
+                return option;
+            }
+            choice -= weight(option);
+        }
+        unreachable!("choice was less than the total weight");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    // Count deals the slow way, by trying every owner for every unknown card.
+    fn brute_force_count(engine: &ClueEngine) -> u128 {
+        let mut count = 0;
+        let solution_possibilities = engine.solution_possibilities();
+        for card1 in solution_possibilities.get(&CardType::Suspect).unwrap() {
+            for card2 in solution_possibilities.get(&CardType::Weapon).unwrap() {
+                for card3 in solution_possibilities.get(&CardType::Room).unwrap() {
+                    let mut dealt = engine.clone();
+                    let solution_index = dealt.number_of_real_players();
+                    dealt.player_data[solution_index].has_cards = vec![*card1, *card2, *card3].into_iter().collect();
+                    let unknown_cards = CardUtils::all_cards().filter(|card| dealt.player_data.iter().all(|player| !player.has_cards.contains(card))).collect::<Vec<Card>>();
+                    count += brute_force_count_from(&mut dealt, &unknown_cards);
+                }
+            }
+        }
+        return count;
+    }
+
+    fn brute_force_count_from(engine: &mut ClueEngine, unknown_cards: &[Card]) -> u128 {
+        if unknown_cards.is_empty() {
+            let consistent = engine.player_data.iter().all(|player| {
+                player.has_cards.len() == player.num_cards.unwrap() as usize
+                    && player.has_cards.is_disjoint(&player.not_has_cards)
+                    && player.possible_cards.iter().all(|clause| !clause.is_disjoint(&player.has_cards))
+            });
+            return if consistent { 1 } else { 0 };
+        }
+        let card = unknown_cards[0];
+        let mut count = 0;
+        for player_index in 0..engine.number_of_real_players() {
+            if engine.player_data[player_index].has_cards.len() < engine.player_data[player_index].num_cards.unwrap() as usize {
+                engine.player_data[player_index].has_cards.insert(card);
+                count += brute_force_count_from(engine, &unknown_cards[1..]);
+                engine.player_data[player_index].has_cards.remove(&card);
+            }
+        }
+        return count;
+    }
+
+    fn make_nearly_solved_engine() -> ClueEngine {
+        // Leaves a handful of unknown cards and a couple of clauses
+        let mut engine = ClueEngine::new(3, None).unwrap();
+        for card in [Card::ProfessorPlum, Card::ColonelMustard, Card::Knife, Card::Candlestick, Card::Hall] {
+            engine.learn_info_on_card(0, card, true, true);
+        }
+        for card in [Card::MrGreen, Card::Revolver, Card::Conservatory, Card::DiningRoom] {
+            engine.learn_info_on_card(1, card, true, true);
+        }
+        for card in [Card::MissScarlet, Card::LeadPipe, Card::Kitchen, Card::Study] {
+            engine.learn_info_on_card(2, card, true, true);
+        }
+        engine.learn_info_on_card(3, Card::Library, false, true);
+        engine.learn_suggest(0, Card::DrOrchid, Card::Rope, Card::Library, Some(1), None);
+        engine.learn_suggest(1, Card::MrsPeacock, Card::Wrench, Card::Lounge, Some(2), None);
+        return engine;
+    }
+
+    #[test]
+    fn test_count_empty_game() {
+        let engine = ClueEngine::new(3, None).unwrap();
+        let counter = DealCounter::new(&engine).unwrap();
+        // 6*6*9 solutions, then 18 cards into three hands of 6
+        let hands = binomial(18, 6) * binomial(12, 6);
+        assert_eq!(324 * hands, counter.total_count());
+        assert_eq!(324, counter.solution_counts().len());
+    }
+
+    #[test]
+    fn test_count_matches_brute_force() {
+        let engine = make_nearly_solved_engine();
+        let counter = DealCounter::new(&engine).unwrap();
+        assert!(counter.total_count() > 0);
+        assert_eq!(brute_force_count(&engine), counter.total_count());
+    }
+
+    #[test]
+    fn test_count_matches_brute_force_overlapping_clauses() {
+        let mut engine = make_nearly_solved_engine();
+        engine.learn_suggest(2, Card::DrOrchid, Card::Wrench, Card::BilliardRoom, Some(1), None);
+        let counter = DealCounter::new(&engine).unwrap();
+        assert_eq!(brute_force_count(&engine), counter.total_count());
+    }
+
+    #[test]
+    fn test_count_inconsistent() {
+        let mut engine = ClueEngine::new(3, None).unwrap();
+        engine.player_data[0].has_cards.insert(Card::Knife);
+        engine.player_data[1].has_cards.insert(Card::Knife);
+        assert_eq!(0, DealCounter::new(&engine).unwrap().total_count());
+    }
+
+    #[test]
+    fn test_count_unknown_number_of_cards() {
+        let engine = ClueEngine::load_from_string("30-.6-.6-.3-.").unwrap();
+        assert!(DealCounter::new(&engine).is_err());
+    }
+
+    #[test]
+    fn test_for_each_deal_lists_every_deal_once() {
+        let engine = make_nearly_solved_engine();
+        let mut counter = DealCounter::new(&engine).unwrap();
+        let mut seen: HashSet<Vec<String>> = HashSet::new();
+        counter.for_each_deal(|hands| {
+            let mut dealt = engine.clone();
+            for (player_index, hand) in hands.iter().enumerate() {
+                dealt.player_data[player_index].has_cards = hand.clone();
+            }
+            assert_eq!(1, brute_force_count_from(&mut dealt, &[]));
+            assert!(seen.insert(hands.iter().map(CardUtils::card_set_to_sorted_string).collect()));
+        });
+        assert_eq!(counter.total_count() as usize, seen.len());
+    }
+
+    #[test]
+    fn test_samples_are_consistent_and_uniform() {
+        let engine = make_nearly_solved_engine();
+        let mut counter = DealCounter::new(&engine).unwrap();
+        let total = counter.total_count();
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut seen: HashMap<Vec<String>, usize> = HashMap::new();
+        let num_samples = 200 * total as usize;
+        for _ in 0..num_samples {
+            let deal = counter.sample(&mut rng).unwrap();
+            let mut dealt = engine.clone();
+            for (player_index, hand) in deal.hands.iter().enumerate() {
+                dealt.player_data[player_index].has_cards = hand.clone();
+            }
+            assert_eq!(1, brute_force_count_from(&mut dealt, &[]));
+            *seen.entry(deal.hands.iter().map(CardUtils::card_set_to_sorted_string).collect()).or_insert(0) += 1;
+        }
+        // Every deal should come up, and about equally often
+        assert_eq!(total as usize, seen.len());
+        for count in seen.values() {
+            assert!(*count > 100 && *count < 300, "count was {}", count);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+// The rules ClueEngine uses to figure out new facts from what it already
+// knows.  Each engine has a list of rules (see ClueEngine::add_deduction_rule()
+// and ClueEngine::set_deduction_rule_enabled()), and they're run whenever
+// something changes until none of them find anything new.
+
+use crate::{Card, CardUtils, ClueEngine, Fact, HittingSetCounter};
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+// What's changed since the rules last ran.
+#[derive(Debug, Clone, Default)]
+pub struct DeductionChanges {
+    // Players whose facts or clauses have changed
+    pub players: BTreeSet<usize>,
+    // Cards we've learned something about
+    pub cards: BTreeSet<Card>,
+}
+
+impl DeductionChanges {
+    pub fn is_empty(self: &Self) -> bool {
+        return self.players.is_empty() && self.cards.is_empty();
+    }
+}
+
+pub trait DeductionRule: Debug + Send + Sync {
+    // Has to be different from the names of all the other rules in an engine.
+    fn name(self: &Self) -> &str;
+
+    // Whether to run this rule when learn_info_on_card() is called with
+    // update_engine set to false.
+    fn runs_on_minimal_update(self: &Self) -> bool {
+        return false;
+    }
+
+    // Adds facts that follow from what engine knows to deduced_facts.  It's
+    // fine to add facts that are already known.  Anything that follows from
+    // what hasn't changed was already found the last time the rule ran, so
+    // rules can use changes to skip work.
+    fn deduce(self: &Self, engine: &ClueEngine, changes: &DeductionChanges, deduced_facts: &mut Vec<Fact>);
+}
+
+// The rules every engine starts with, in the order they run.
+pub fn default_deduction_rules() -> Vec<Arc<dyn DeductionRule>> {
+    return vec![Arc::new(ClauseRule {}), Arc::new(CardOwnerRule {}), Arc::new(SolutionRule {}), Arc::new(OverlappingClausesRule {})];
+}
+
+// Deductions we can make from a single player's clauses and number of cards.
+#[derive(Debug)]
+pub struct ClauseRule {
+}
+
+impl DeductionRule for ClauseRule {
+    fn name(self: &Self) -> &str {
+        return "clauses";
+    }
+
+    fn runs_on_minimal_update(self: &Self) -> bool {
+        return true;
+    }
+
+    fn deduce(self: &Self, engine: &ClueEngine, changes: &DeductionChanges, deduced_facts: &mut Vec<Fact>) {
+        for player_index in changes.players.iter() {
+            Self::examine_clauses(engine, *player_index, deduced_facts);
+        }
+    }
+}
+
+impl ClauseRule {
+    fn examine_clauses(engine: &ClueEngine, player_index: usize, deduced_facts: &mut Vec<Fact>) {
+        let player = &engine.player_data[player_index];
+        for card in player.possible_cards.single_card_clauses() {
+            // We have this card!
+            deduced_facts.push(Fact { player_index, card, has_card: true });
+        }
+        if let Some(number_of_cards) = player.num_cards {
+            if number_of_cards == player.has_cards.len() as u8 {
+                // All cards are accounted for.
+                for other_card in CardUtils::all_cards() {
+                    if player.has_card(other_card) == None {
+                        deduced_facts.push(Fact { player_index, card: other_card, has_card: false });
+                    }
+                }
+            }
+            else if player.has_cards.len() + player.possible_cards.len() > (number_of_cards as usize) {
+                // We may be able to figure out something
+                let num_accounted_for = number_of_cards as isize - player.has_cards.len() as isize;
+                let clause_masks = player.possible_cards.masks();
+                let card_in_any_clause = clause_masks.iter().fold(0, |mask, clause| mask | clause);
+                // Removing different cards often leaves the same clauses
+                // further down, so share the work.
+                let mut hitting_set_counter = HittingSetCounter::default();
+                for test_card in CardUtils::all_cards().filter(|card| card_in_any_clause & (1 << (*card as u32)) != 0) {
+                    // See if we could have this card, by contradiction.
+                    // Assume we don't have this card.  Remove it from
+                    // all clauses.
+                    let new_clauses = clause_masks.iter().map(|clause| clause & !(1 << (test_card as u32))).collect::<Vec<u32>>();
+                    // See if it's still possible to satisfy the rest of the clauses.
+                    let is_possible = hitting_set_counter.can_satisfy(&new_clauses, num_accounted_for);
+                    if !is_possible {
+                        // We found a contradiction if we don't have this card,
+                        // so we must have this card.
+                        deduced_facts.push(Fact { player_index, card: test_card, has_card: true });
+                    }
+                }
+            }
+        }
+    }
+}
+
+// If someone has a card, no one else does.  If everyone but one player
+// doesn't have a card, that player has it.
+#[derive(Debug)]
+pub struct CardOwnerRule {
+}
+
+impl DeductionRule for CardOwnerRule {
+    fn name(self: &Self) -> &str {
+        return "card_owner";
+    }
+
+    fn deduce(self: &Self, engine: &ClueEngine, changes: &DeductionChanges, deduced_facts: &mut Vec<Fact>) {
+        for card in changes.cards.iter() {
+            Self::check_for_all_players_but_one_dont_have_this_card(engine, *card, deduced_facts);
+        }
+    }
+}
+
+impl CardOwnerRule {
+    fn check_for_all_players_but_one_dont_have_this_card(engine: &ClueEngine, card: Card, deduced_facts: &mut Vec<Fact>) {
+        let mut someone_has_card = false;
+        let mut number_who_dont_have_card = 0;
+        let mut player_who_might_have_card = None;
+        // - Check also for all cards except one in a category are
+        // accounted for.
+        for i in 0..engine.player_data.len() {
+            let player = &engine.player_data[i];
+            let has_card = player.has_card(card);
+            match has_card {
+                Some(true) => {
+                    // Someone has the card, so the solution is not this.
+                    someone_has_card = true;
+                    break;
+                },
+                Some(false) => {
+                    number_who_dont_have_card += 1;
+                },
+                None => {
+                    // We only look at this if there's only one person who could have this card.
+                    player_who_might_have_card = Some(i);
+                }
+            }
+        }
+        if !someone_has_card && number_who_dont_have_card == engine.number_of_real_players() {
+            // Every player except one doesn't have this card, so we know the player has it.
+            if let Some(player_index) = player_who_might_have_card {
+                deduced_facts.push(Fact { player_index, card, has_card: true });
+            }
+        }
+        else if someone_has_card {
+            // Someone has this card, so no one else does. (including solution)
+            for i in 0..engine.player_data.len() {
+                let player = &engine.player_data[i];
+                if player.has_card(card) == None {
+                    deduced_facts.push(Fact { player_index: i, card, has_card: false });
+                }
+            }
+        }
+    }
+}
+
+// If every card but one of a type is owned by a player, the last one is
+// the solution.
+#[derive(Debug)]
+pub struct SolutionRule {
+}
+
+impl DeductionRule for SolutionRule {
+    fn name(self: &Self) -> &str {
+        return "solution";
+    }
+
+    fn deduce(self: &Self, engine: &ClueEngine, _changes: &DeductionChanges, deduced_facts: &mut Vec<Fact>) {
+        for card_type in CardUtils::all_card_types() {
+            let all_cards = CardUtils::cards_of_type(*card_type).collect::<Vec<Card>>();
+            let mut solution_card: Option<Card> = None;
+            let mut is_solution = true;
+            for test_card in all_cards.iter() {
+                // See if anyone has this card
+                let card_owned = engine.player_data.iter().any(|player| player.has_card(*test_card) == Some(true));
+                if !card_owned {
+                    // If there's another possibility, we don't know which is
+                    // right.
+                    if solution_card != None {
+                        solution_card = None;
+                        is_solution = false;
+                    } else {
+                        solution_card = Some(*test_card);
+                    }
+                }
+            }
+            if is_solution && solution_card != None {
+                // There's only one possibility, so this must be it!
+                let solution = solution_card.unwrap();
+                if engine.solution_player().has_card(solution) == None {
+                    // also check to make sure we don't have another one in this category
+                    // (if this happened, we're inconsistent already, just move on)
+                    if all_cards.iter().all(|c| !engine.solution_player().has_cards.contains(c)) {
+                        deduced_facts.push(Fact { player_index: engine.number_of_real_players(), card: solution, has_card: true });
+                    }
+                }
+            }
+        }
+    }
+}
+
+// This is Hall's theorem applied to the clauses: if k players each have a
+// clause and those clauses only use k cards between them, then each of
+// those players must have a different one of those cards (otherwise there
+// is no matching of players to cards).  So no one else can have any of
+// those cards.  The clauses don't have to be identical, just overlapping.
+#[derive(Debug)]
+pub struct OverlappingClausesRule {
+}
+
+impl DeductionRule for OverlappingClausesRule {
+    fn name(self: &Self) -> &str {
+        return "overlapping_clauses";
+    }
+
+    fn deduce(self: &Self, engine: &ClueEngine, _changes: &DeductionChanges, deduced_facts: &mut Vec<Fact>) {
+        let clause_masks = (0..engine.number_of_real_players()).map(|idx| {
+            let mut masks = engine.player_data[idx].possible_cards.masks().to_vec();
+            masks.sort_unstable();
+            masks.dedup();
+            masks
+        }).collect::<Vec<Vec<u32>>>();
+        let mut tight_groups = vec![];
+        Self::find_tight_clause_groups(&clause_masks, 0, 0, 0, 0, &mut tight_groups);
+        for (players_mask, cards_mask) in tight_groups {
+            for idx in 0..(engine.number_of_real_players() + 1) {
+                if players_mask & (1 << idx) != 0 {
+                    continue;
+                }
+                for card in CardUtils::all_cards().filter(|card| cards_mask & (1 << (*card as u32)) != 0) {
+                    if engine.player_data[idx].has_card(card) == None {
+                        deduced_facts.push(Fact { player_index: idx, card, has_card: false });
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl OverlappingClausesRule {
+    // Finds every group of players (picking at most one clause from each) whose
+    // chosen clauses use exactly as many cards as there are players in the group.
+    fn find_tight_clause_groups(clause_masks: &Vec<Vec<u32>>, player_index: usize, players_mask: u32, num_players: u32, cards_mask: u32, tight_groups: &mut Vec<(u32, u32)>) {
+        if num_players > 0 && cards_mask.count_ones() == num_players {
+            tight_groups.push((players_mask, cards_mask));
+        }
+        if player_index == clause_masks.len() {
+            return;
+        }
+        // Adding a player adds at most one to the number of players, so if we
+        // already have too many cards we can never get back to a tight group.
+        let players_left = (clause_masks.len() - player_index) as u32;
+        if cards_mask.count_ones() > num_players + players_left {
+            return;
+        }
+        Self::find_tight_clause_groups(clause_masks, player_index + 1, players_mask, num_players, cards_mask, tight_groups);
+        for clause_mask in clause_masks[player_index].iter() {
+            Self::find_tight_clause_groups(clause_masks, player_index + 1, players_mask | (1 << player_index), num_players + 1, cards_mask | clause_mask, tight_groups);
+        }
+    }
+}
@@ -1,15 +1,90 @@
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
-use std::{collections::HashSet, collections::HashMap, iter::Peekable, str::Chars};
+use std::{collections::HashSet, collections::HashMap, collections::VecDeque, iter::Peekable, str::Chars};
 use std::cmp::min;
 use std::iter::FromIterator;
-use rand::{seq::SliceRandom, prelude::ThreadRng};
+use rand::{seq::SliceRandom, Rng};
 use rand::thread_rng;
 use rayon::prelude::*;
+use std::sync::{Arc, Mutex, OnceLock};
+
+pub mod board;
+pub mod change_set;
+pub mod clause_store;
+pub mod cnf;
+pub mod deal_counter;
+pub mod deduction_rules;
+pub mod opponent_model;
+pub mod planner;
+pub mod refutation_model;
+pub mod robust;
+pub mod sat;
+pub mod simulation_cache;
+pub mod simulation_config;
+pub mod soft_evidence;
+pub mod solution_distribution;
+mod simulation_state;
+pub mod validation;
+pub mod what_if;
+pub use board::{Board, BoardPosition};
+pub use change_set::ChangeSet;
+pub use clause_store::{ClauseChanges, ClauseStore};
+pub use cnf::ClueCnf;
+pub use deal_counter::DealCounter;
+pub use deduction_rules::{DeductionChanges, DeductionRule};
+pub use opponent_model::{OpponentModels, PublicSuggestion, Threat};
+pub use planner::{MovementPlanner, RoomSuggestion, TurnChoice};
+pub use refutation_model::{RecordedGame, RefutationModel, RefutationObservation};
+pub use robust::{Observation, ObservationLog, RobustCandidate, RobustInference};
+pub use sat::SatSolver;
+pub use simulation_cache::SimulationCache;
+pub use simulation_config::{SimulationConfig, SimulationConfigBuilder};
+pub use soft_evidence::{SoftEvidence, WeightedSimulationData};
+pub use solution_distribution::SolutionDistribution;
+use simulation_state::SimulationState;
+pub use validation::Violation;
+pub use what_if::{SuggestionResponse, WhatIfResult};
 
 pub type CardSet = HashSet<Card>;
 pub type SimulationData = HashMap<Card, Vec<usize>>;
 
+// How do_simulation_with_strategy() picks deals to count.
+#[derive(Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Copy, Clone)]
+pub enum SamplingStrategy {
+    // Try the same number of random deals for every possible solution.
+    FixedSolutions,
+    // Pick a possible solution at random for every deal.
+    RandomSolutions,
+    // Pick uniformly from all consistent deals, using a DealCounter.
+    ExactUniform,
+    // If there aren't too many consistent deals, count every one of them
+    // (see ClueEngine::enumerate_deals()).  Otherwise same as ExactUniform.
+    Exhaustive,
+}
+
+impl SamplingStrategy {
+    pub fn all() -> [SamplingStrategy; 4] {
+        return [SamplingStrategy::FixedSolutions, SamplingStrategy::RandomSolutions, SamplingStrategy::ExactUniform, SamplingStrategy::Exhaustive];
+    }
+
+    // The inverse of formatting with {:?}
+    pub fn from_name(name: &str) -> Result<SamplingStrategy, String> {
+        return SamplingStrategy::all().iter().find(|strategy| format!("{:?}", strategy) == name).copied().ok_or(format!("Unknown sampling strategy \"{}\"", name));
+    }
+}
+
+// How much work learn_info_on_card(), learn_has_one_of_cards() and
+// learn_suggest() do to figure out new facts.
+#[derive(Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Copy, Clone)]
+pub enum DeductionMode {
+    // Only use the deduction rules.  These are fast, but there are some
+    // facts they can't figure out.
+    Rules,
+    // Also learn every fact that follows from what we know (see
+    // ClueEngine::learn_entailed_facts()).
+    Complete,
+}
+
 #[derive(Clone,Debug)]
 struct FastSimulationData {
     num_players: usize,
@@ -18,6 +93,126 @@ struct FastSimulationData {
     // index of (card, player) count is
     // card * num_players + player
     data: Vec<usize>,
+    // Only gathered if asked for, since it's a lot more work per simulation
+    joint: Option<JointSimulationData>,
+    // How many times each solution (as a bitmask of cards) came up, if asked for
+    solution_counts: Option<HashMap<u32, usize>>,
+}
+
+// Co-ownership counts gathered alongside the per-card counts in SimulationData.
+#[derive(Clone,Debug)]
+pub struct JointSimulationData {
+    num_players: usize,
+    // index of ((card_a, player_a), (card_b, player_b)) count is
+    // (card_a * num_players + player_a) * (CARD_LAST * num_players) + (card_b * num_players + player_b)
+    pair_counts: Vec<usize>,
+    // One map per player (including the solution) from a hand (as a bitmask
+    // of cards) to how many times that hand came up.
+    hand_counts: Vec<HashMap<u32, usize>>,
+    num_simulations: usize,
+}
+
+impl JointSimulationData {
+    fn new(num_players: usize) -> Self {
+        let num_entries = num_players * CARD_LAST as usize;
+        JointSimulationData {
+            num_players,
+            pair_counts: vec![0; num_entries * num_entries],
+            hand_counts: vec![HashMap::new(); num_players],
+            num_simulations: 0,
+        }
+    }
+
+    fn entry_index(self: &JointSimulationData, card: Card, player_index: usize) -> usize {
+        return card as usize * self.num_players + player_index;
+    }
+
+    fn pair_index(self: &JointSimulationData, card_a: Card, player_a: usize, card_b: Card, player_b: usize) -> usize {
+        return self.entry_index(card_a, player_a) * (CARD_LAST as usize * self.num_players) + self.entry_index(card_b, player_b);
+    }
+
+    // hands has one hand per player, with the solution last.
+    fn record_hands<'a, I: Iterator<Item=&'a CardSet>>(self: &mut JointSimulationData, hands: I) {
+        let hand_masks = hands.map(CardUtils::card_set_to_mask).collect::<Vec<u32>>();
+        self.record_hand_masks(&hand_masks);
+    }
+
+    // Like record_hands(), but with each hand as a mask of cards.
+    fn record_hand_masks(self: &mut JointSimulationData, hand_masks: &[u32]) {
+        let num_entries = CARD_LAST as usize * self.num_players;
+        let mut owned_entries = [0; CARD_LAST as usize];
+        let mut num_owned_entries = 0;
+        for (player_index, hand_mask) in hand_masks.iter().enumerate() {
+            for card in CardUtils::cards_in_mask(*hand_mask) {
+                owned_entries[num_owned_entries] = self.entry_index(card, player_index);
+                num_owned_entries += 1;
+            }
+            *self.hand_counts[player_index].entry(*hand_mask).or_insert(0) += 1;
+        }
+        for &entry_a in owned_entries[..num_owned_entries].iter() {
+            for &entry_b in owned_entries[..num_owned_entries].iter() {
+                self.pair_counts[entry_a * num_entries + entry_b] += 1;
+            }
+        }
+        self.num_simulations += 1;
+    }
+
+    fn accumulate_from(self: &mut JointSimulationData, source: &JointSimulationData) {
+        for i in 0..self.pair_counts.len() {
+            self.pair_counts[i] += source.pair_counts[i];
+        }
+        for player_index in 0..self.hand_counts.len() {
+            for (hand, count) in source.hand_counts[player_index].iter() {
+                *self.hand_counts[player_index].entry(*hand).or_insert(0) += count;
+            }
+        }
+        self.num_simulations += source.num_simulations;
+    }
+
+    pub fn num_simulations(self: &JointSimulationData) -> usize {
+        return self.num_simulations;
+    }
+
+    // Number of consistent simulations where player_a had card_a and player_b had card_b.
+    pub fn count_both(self: &JointSimulationData, card_a: Card, player_a: usize, card_b: Card, player_b: usize) -> usize {
+        return self.pair_counts[self.pair_index(card_a, player_a, card_b, player_b)];
+    }
+
+    pub fn probability(self: &JointSimulationData, card: Card, player_index: usize) -> f64 {
+        return self.probability_both(card, player_index, card, player_index);
+    }
+
+    pub fn probability_both(self: &JointSimulationData, card_a: Card, player_a: usize, card_b: Card, player_b: usize) -> f64 {
+        if self.num_simulations == 0 {
+            return 0.0;
+        }
+        return self.count_both(card_a, player_a, card_b, player_b) as f64 / self.num_simulations as f64;
+    }
+
+    // The correlation (phi coefficient) between "player_a has card_a" and
+    // "player_b has card_b".  Positive means the cards tend to move together.
+    // Returns None if either one is always or never true in the simulations,
+    // since then there's nothing to correlate.
+    pub fn correlation(self: &JointSimulationData, card_a: Card, player_a: usize, card_b: Card, player_b: usize) -> Option<f64> {
+        let p_a = self.probability(card_a, player_a);
+        let p_b = self.probability(card_b, player_b);
+        let variance = p_a * (1.0 - p_a) * p_b * (1.0 - p_b);
+        if variance <= 0.0 {
+            return None;
+        }
+        let p_both = self.probability_both(card_a, player_a, card_b, player_b);
+        return Some((p_both - p_a * p_b) / variance.sqrt());
+    }
+
+    // All the hands this player was dealt in the simulations, most common first.
+    pub fn hand_distribution(self: &JointSimulationData, player_index: usize) -> Vec<(CardSet, usize)> {
+        let mut hands = self.hand_counts[player_index].iter().map(|(&hand_mask, &count)| {
+            let hand = CardUtils::cards_in_mask(hand_mask).collect::<CardSet>();
+            (hand, count)
+        }).collect::<Vec<(CardSet, usize)>>();
+        hands.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| CardUtils::card_set_to_sorted_string(&a.0).cmp(&CardUtils::card_set_to_sorted_string(&b.0))));
+        return hands;
+    }
 }
 
 impl From<&FastSimulationData> for SimulationData {
@@ -37,7 +232,31 @@ impl FastSimulationData {
         data.resize(engine.player_data.len() * CARD_LAST as usize, 0);
         FastSimulationData {
             num_players: engine.player_data.len(),
-            data
+            data,
+            joint: None,
+            solution_counts: None
+        }
+    }
+
+    fn new_with_joint_data(engine: &ClueEngine) -> Self {
+        let mut simulation_data = FastSimulationData::new(engine);
+        simulation_data.joint = Some(JointSimulationData::new(engine.player_data.len()));
+        return simulation_data;
+    }
+
+    fn new_with_solution_counts(engine: &ClueEngine) -> Self {
+        let mut simulation_data = FastSimulationData::new(engine);
+        simulation_data.solution_counts = Some(HashMap::new());
+        return simulation_data;
+    }
+
+    // An empty FastSimulationData that gathers the same things as this one.
+    fn new_like(self: &FastSimulationData) -> Self {
+        FastSimulationData {
+            num_players: self.num_players,
+            data: vec![0; self.data.len()],
+            joint: self.joint.as_ref().map(|joint| JointSimulationData::new(joint.num_players)),
+            solution_counts: self.solution_counts.as_ref().map(|_| HashMap::new())
         }
     }
 
@@ -59,10 +278,49 @@ impl FastSimulationData {
         self.data[(card as usize * self.num_players) + player_index] += 1;
     }
 
+    // Count the cards in a consistent deal where all cards are assigned,
+    // with each hand as a mask of cards.
+    fn record_hand_masks(self: &mut FastSimulationData, hand_masks: &[u32]) {
+        for (player_index, hand_mask) in hand_masks.iter().enumerate() {
+            for card in CardUtils::cards_in_mask(*hand_mask) {
+                self.increment_entry(card, player_index);
+            }
+        }
+        if let Some(joint) = self.joint.as_mut() {
+            joint.record_hand_masks(hand_masks);
+        }
+        if let Some(solution_counts) = self.solution_counts.as_mut() {
+            *solution_counts.entry(*hand_masks.last().unwrap()).or_insert(0) += 1;
+        }
+    }
+
+    // Like record_hand_masks(), but for a Deal.
+    fn record_hands(self: &mut FastSimulationData, hands: &[CardSet]) {
+        for (player_index, hand) in hands.iter().enumerate() {
+            for card in hand.iter() {
+                self.increment_entry(*card, player_index);
+            }
+        }
+        if let Some(joint) = self.joint.as_mut() {
+            joint.record_hands(hands.iter());
+        }
+        if let Some(solution_counts) = self.solution_counts.as_mut() {
+            *solution_counts.entry(CardUtils::card_set_to_mask(hands.last().unwrap())).or_insert(0) += 1;
+        }
+    }
+
     fn accumulate_from(self: &mut FastSimulationData, source: &FastSimulationData) {
         for i in 0..self.data.len() {
             self.data[i] += source.data[i];
         }
+        if let (Some(joint), Some(source_joint)) = (self.joint.as_mut(), source.joint.as_ref()) {
+            joint.accumulate_from(source_joint);
+        }
+        if let (Some(solution_counts), Some(source_solution_counts)) = (self.solution_counts.as_mut(), source.solution_counts.as_ref()) {
+            for (solution, count) in source_solution_counts.iter() {
+                *solution_counts.entry(*solution).or_insert(0) += count;
+            }
+        }
     }
 }
 
@@ -114,6 +372,35 @@ impl From<bool> for UpdateEngineMode {
     }
 }
 
+// A single thing we've learned: whether a player has a card.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct Fact {
+    pub player_index: usize,
+    pub card: Card,
+    pub has_card: bool
+}
+
+// The state of one round of deductions.  Facts that haven't been applied yet
+// are queued up, and the deduction rules run on what's changed once the queue
+// is empty.
+struct Propagation<'a> {
+    mode: UpdateEngineMode,
+    pending: VecDeque<Fact>,
+    changes: DeductionChanges,
+    change_set: &'a mut ChangeSet
+}
+
+impl<'a> Propagation<'a> {
+    fn new(mode: UpdateEngineMode, change_set: &'a mut ChangeSet) -> Propagation<'a> {
+        return Propagation {
+            mode,
+            pending: VecDeque::new(),
+            changes: DeductionChanges::default(),
+            change_set
+        };
+    }
+}
+
 pub struct CardUtils {
 }
 
@@ -167,6 +454,79 @@ impl CardUtils {
         chars.sort();
         return chars.into_iter().collect();
     }
+
+    // Bit n of the mask is set if the card with value n is in the set.
+    pub fn card_set_to_mask(card_set: &CardSet) -> u32 {
+        return card_set.iter().fold(0, |mask, card| mask | (1 << (*card as u32)));
+    }
+
+    // The cards in a mask made by card_set_to_mask(), in order.
+    pub fn cards_in_mask(mask: u32) -> impl Iterator<Item=Card> {
+        let mut remaining_mask = mask;
+        return std::iter::from_fn(move || {
+            if remaining_mask == 0 {
+                return None;
+            }
+            let card_index = remaining_mask.trailing_zeros();
+            // Clear the lowest bit
+            remaining_mask &= remaining_mask - 1;
+            return Some(FromPrimitive::from_u32(card_index).unwrap());
+        });
+    }
+}
+
+// Finds the smallest number of cards needed to have at least one card in
+// each of a list of clauses.  Clauses are masks of cards (see
+// CardUtils::card_set_to_mask()), and results are remembered, so checking
+// lots of similar lists of clauses only does the work once.
+#[derive(Debug, Default)]
+struct HittingSetCounter {
+    memo: HashMap<Vec<u32>, Option<usize>>,
+}
+
+impl HittingSetCounter {
+    // Returns None if there's an empty clause, since then no set of cards works.
+    fn min_cards(self: &mut Self, clauses: &[u32]) -> Option<usize> {
+        let mut key = clauses.to_vec();
+        key.sort_unstable();
+        key.dedup();
+        if key.is_empty() {
+            return Some(0);
+        }
+        if key[0] == 0 {
+            return None;
+        }
+        if let Some(result) = self.memo.get(&key) {
+            return *result;
+        }
+        // One of the cards in the smallest clause has to be used, so try
+        // each of them.
+        let smallest_clause = *key.iter().min_by_key(|clause| clause.count_ones()).unwrap();
+        let mut best = None;
+        let mut cards_left = smallest_clause;
+        while cards_left != 0 {
+            let card_bit = cards_left & cards_left.wrapping_neg();
+            cards_left &= !card_bit;
+            let other_clauses = key.iter().filter(|clause| *clause & card_bit == 0).copied().collect::<Vec<u32>>();
+            if let Some(number_of_cards) = self.min_cards(&other_clauses) {
+                best = Some(best.map_or(number_of_cards + 1, |best| min(best, number_of_cards + 1)));
+            }
+        }
+        self.memo.insert(key, best);
+        return best;
+    }
+
+    // Returns whether we can have one card from each clause using at most
+    // number_of_cards cards.
+    fn can_satisfy(self: &mut Self, clauses: &[u32], number_of_cards: isize) -> bool {
+        if clauses.is_empty() {
+            return true;
+        }
+        return match self.min_cards(clauses) {
+            Some(min_cards) => min_cards as isize <= number_of_cards,
+            None => false
+        };
+    }
 }
 
 // https://wduquette.github.io/parsing-strings-into-slices/
@@ -228,8 +588,10 @@ pub struct PlayerData {
     // A set of cards that the player is known not to have
     pub not_has_cards: CardSet,
     // A list of clauses.  Each clause is a set of cards, one of which
-    // the player is known to have.
-    pub possible_cards: Vec<CardSet>,
+    // the player is known to have.  No clause is a subset of another.
+    // (This used to be a Vec<CardSet>; ClauseStore derefs to a slice of
+    // them, so reading it works the same way.)
+    pub possible_cards: ClauseStore,
     pub is_solution_player: bool,
     // None means we don't know how many cards
     pub num_cards: Option<u8>
@@ -240,13 +602,23 @@ impl PlayerData {
         return PlayerData {
             has_cards: HashSet::new(),
             not_has_cards: HashSet::new(),
-            possible_cards: vec!(),
+            possible_cards: ClauseStore::new(),
             is_solution_player,
             num_cards
         };
     }
 
     pub fn write_to_string(self: &PlayerData) -> String {
+        return self.write_to_string_internal(false);
+    }
+
+    // Like write_to_string(), but the clauses are sorted, so players who
+    // we know the same things about always give the same string.
+    pub fn write_to_canonical_string(self: &PlayerData) -> String {
+        return self.write_to_string_internal(true);
+    }
+
+    fn write_to_string_internal(self: &PlayerData, sort_clauses: bool) -> String {
         let mut s = String::from("");
 
         let num_cards_to_write = self.num_cards.unwrap_or(0);
@@ -255,9 +627,13 @@ impl PlayerData {
         s += &CardUtils::card_set_to_sorted_string(&self.has_cards);
         s += "-";
         s += &CardUtils::card_set_to_sorted_string(&self.not_has_cards);
-        for possible_card_group in (&self.possible_cards).into_iter() {
+        let mut clause_strings = self.possible_cards.iter().map(CardUtils::card_set_to_sorted_string).collect::<Vec<String>>();
+        if sort_clauses {
+            clause_strings.sort();
+        }
+        for clause_string in clause_strings {
             s += "-";
-            s += &CardUtils::card_set_to_sorted_string(&possible_card_group);
+            s += &clause_string;
         }
         s += ".";
         return s;
@@ -275,42 +651,109 @@ impl PlayerData {
     }
 
 
+    // possible_cards never has extraneous clauses anymore, so there's nothing
+    // to do.
+    #[deprecated(note = "possible_cards is a ClauseStore, which removes extraneous clauses itself")]
     pub fn eliminate_extraneous_clauses(self: &mut PlayerData) {
-        PlayerData::eliminate_extraneous_clauses_possible_cards(&mut self.possible_cards);
     }
+
     pub fn eliminate_extraneous_clauses_possible_cards(possible_cards: &mut Vec<CardSet>) {
-        let mut need_to_call_again = false;
-        // This is O(n^2), but hopefully there aren't too many of these
-        'outer: for i in 0..possible_cards.len() {
-            for j in (i+1)..possible_cards.len() {
-                let clause_1 = &possible_cards[i];
-                let clause_2 = &possible_cards[j];
-                if clause_1.is_subset(clause_2) {
-                    // clause_2 is extraneous
-                    possible_cards.remove(j);
-                    need_to_call_again = true;
-                    break 'outer;
-                }
-                else if clause_1.is_superset(clause_2) {
-                    // clause_1 is extraneous
-                    possible_cards.remove(i);
-                    need_to_call_again = true;
-                    break 'outer;
-                }
-            }
+        let mut clause_store = ClauseStore::new();
+        for clause in possible_cards.drain(..) {
+            clause_store.add(clause);
+        }
+        possible_cards.extend(clause_store.iter().cloned());
+    }
+}
+
+// A complete assignment of cards, as returned by ClueEngine::sample_deals().
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Deal {
+    // One hand per player, with the solution last.
+    pub hands: Vec<CardSet>,
+}
+
+impl Deal {
+    pub fn solution(self: &Deal) -> &CardSet {
+        return self.hands.last().unwrap();
+    }
+
+    pub fn owner_of_card(self: &Deal, card: Card) -> Option<usize> {
+        return self.hands.iter().position(|hand| hand.contains(&card));
+    }
+}
+
+pub struct DealSampler<'a, R: Rng> {
+    engine: &'a ClueEngine,
+    rng: R,
+    remaining: usize,
+    // Indexed in the same order as CardUtils::all_card_types()
+    solution_possibilities: Vec<Vec<Card>>,
+    // None if we don't know how many cards everyone has
+    simulation_state: Option<SimulationState>,
+    // Built the first time each solution is picked
+    solution_simulation_states: HashMap<[Card; 3], SimulationState>,
+}
+
+impl<'a, R: Rng> DealSampler<'a, R> {
+    // If we can't find a consistent deal after this many tries, give up.
+    const MAX_ATTEMPTS_PER_DEAL: usize = 100000;
+
+    fn try_one_deal(self: &mut Self) -> Option<Deal> {
+        let mut solution = [Card::ProfessorPlum; 3];
+        for (solution_card, possibilities) in solution.iter_mut().zip(self.solution_possibilities.iter()) {
+            *solution_card = *possibilities.choose(&mut self.rng).unwrap();
         }
-        if need_to_call_again {
-            // The easiest way to check without messing up the loop is
-            // to start over, although it's kinda slow.  But I don't
-            // expect there to be tons of extraneous clauses.
-            PlayerData::eliminate_extraneous_clauses_possible_cards(possible_cards);
+        let base_simulation_state = self.simulation_state.as_ref()?;
+        let simulation_state = self.solution_simulation_states.entry(solution).or_insert_with(|| base_simulation_state.with_solution(&solution));
+        if !simulation_state.deal(&mut self.rng) {
+            return None;
         }
+        return Some(Deal { hands: simulation_state.hands().iter().map(|hand_mask| CardUtils::cards_in_mask(*hand_mask).collect()).collect() });
     }
 }
 
+impl<'a, R: Rng> Iterator for DealSampler<'a, R> {
+    type Item = Deal;
+
+    fn next(self: &mut Self) -> Option<Deal> {
+        if self.remaining == 0 {
+            return None;
+        }
+        if self.engine.player_data.iter().any(|player| player.num_cards == None) || self.solution_possibilities.iter().any(|cards| cards.is_empty()) {
+            // Can't deal if we don't know how many cards everyone has,
+            // or if there's no possible solution.
+            self.remaining = 0;
+            return None;
+        }
+        for _ in 0..Self::MAX_ATTEMPTS_PER_DEAL {
+            if let Some(deal) = self.try_one_deal() {
+                self.remaining -= 1;
+                return Some(deal);
+            }
+        }
+        // Probably there are no consistent deals at all
+        self.remaining = 0;
+        return None;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DeductionRuleEntry {
+    rule: Arc<dyn DeductionRule>,
+    enabled: bool
+}
+
 #[derive(Debug, Clone)]
 pub struct ClueEngine {
     pub player_data: Vec<PlayerData>,
+    pub deduction_mode: DeductionMode,
+    deduction_rules: Vec<DeductionRuleEntry>,
+    // The cards we've shown each player when refuting their suggestions.
+    cards_shown: Vec<CardSet>,
+    // If we're keeping track of what opponents know (see track_opponents()),
+    // which player we are and every suggestion since then.
+    opponent_log: Option<(usize, Vec<PublicSuggestion>)>,
 }
 
 impl ClueEngine {
@@ -344,7 +787,36 @@ impl ClueEngine {
             let player_data = PlayerData::new(Some(number_of_cards), i == number_of_players);
             player_datas.push(player_data);
         }
-        Ok(ClueEngine { player_data: player_datas })
+        let deduction_rules = deduction_rules::default_deduction_rules().into_iter().map(|rule| DeductionRuleEntry { rule, enabled: true }).collect();
+        let cards_shown = vec![HashSet::new(); number_of_players as usize];
+        Ok(ClueEngine { player_data: player_datas, deduction_mode: DeductionMode::Rules, deduction_rules, cards_shown, opponent_log: None })
+    }
+
+    // Makes an engine where we know who has every card.
+    pub fn from_deal(deal: &Deal) -> Result<ClueEngine, String> {
+        if deal.hands.len() < 2 {
+            return Err(String::from("A deal needs at least one player and the solution!"));
+        }
+        let number_of_players = deal.hands.len() - 1;
+        for card in CardUtils::all_cards() {
+            let number_of_owners = deal.hands.iter().filter(|hand| hand.contains(&card)).count();
+            if number_of_owners != 1 {
+                return Err(format!("Card {} is in {} hands (expected 1)", CardUtils::char_from_card(card), number_of_owners));
+            }
+        }
+        for card_type in CardUtils::all_card_types() {
+            if CardUtils::cards_of_type(*card_type).filter(|card| deal.solution().contains(card)).count() != 1 {
+                return Err(format!("Solution must have exactly one card of type {:?}", card_type));
+            }
+        }
+        let cards_per_player = deal.hands[..number_of_players].iter().map(|hand| hand.len() as u8).collect::<Vec<u8>>();
+        let mut engine = ClueEngine::new(number_of_players as u8, Some(&cards_per_player))?;
+        for (player_index, hand) in deal.hands.iter().enumerate() {
+            for card in CardUtils::all_cards() {
+                engine.learn_info_on_card(player_index, card, hand.contains(&card), false);
+            }
+        }
+        return Ok(engine);
     }
 
     pub fn number_of_real_players(self: &Self) -> usize {
@@ -356,6 +828,28 @@ impl ClueEngine {
         &self.player_data[self.number_of_real_players()]
     }
 
+    // Adds a rule that runs after the existing ones.  Rules are run when
+    // facts change, so this doesn't learn anything by itself.
+    pub fn add_deduction_rule(self: &mut Self, rule: Arc<dyn DeductionRule>) -> Result<(), String> {
+        if self.deduction_rules.iter().any(|entry| entry.rule.name() == rule.name()) {
+            return Err(format!("There's already a deduction rule named '{}'", rule.name()));
+        }
+        self.deduction_rules.push(DeductionRuleEntry { rule, enabled: true });
+        return Ok(());
+    }
+
+    pub fn set_deduction_rule_enabled(self: &mut Self, name: &str, enabled: bool) -> Result<(), String> {
+        let entry = self.deduction_rules.iter_mut().find(|entry| entry.rule.name() == name).ok_or(format!("No deduction rule named '{}'", name))?;
+        entry.enabled = enabled;
+        return Ok(());
+    }
+
+    // Returns the names of the deduction rules in the order they run, and
+    // whether each one is enabled.
+    pub fn deduction_rules(self: &Self) -> Vec<(&str, bool)> {
+        return self.deduction_rules.iter().map(|entry| (entry.rule.name(), entry.enabled)).collect();
+    }
+
     pub fn solution_player_mut(self: &mut Self) -> &mut PlayerData {
         let index = self.number_of_real_players();
         &mut self.player_data[index]
@@ -377,12 +871,53 @@ impl ClueEngine {
         return num_cards as u8;
     }
 
+    // After the players, for each player we've shown cards to:
+    // '~'
+    // the player's index
+    // one letter per card we've shown them
+    // Then, if we're tracking opponents:
+    // '|'
+    // our player index
+    // for each suggestion, the suggesting player's index, one letter per
+    // suggested card, the refuting player's index (or '-') and the card
+    // shown (or '-')
     pub fn write_to_string(self: &ClueEngine) -> String {
         let mut s = String::from("");
         s += &(self.number_of_real_players()).to_string();
         for player in self.player_data.iter() {
             s += &player.write_to_string();
         }
+        for (player_index, cards) in self.cards_shown.iter().enumerate() {
+            if !cards.is_empty() {
+                s += "~";
+                s += &player_index.to_string();
+                s += &CardUtils::card_set_to_sorted_string(cards);
+            }
+        }
+        if let Some((my_player_index, suggestions)) = &self.opponent_log {
+            s += "|";
+            s += &my_player_index.to_string();
+            for suggestion in suggestions {
+                s += &suggestion.suggesting_player_index.to_string();
+                s.extend(suggestion.cards.iter().map(|card| CardUtils::char_from_card(*card)));
+                s += &suggestion.refuting_player_index.map_or(String::from("-"), |player_index| player_index.to_string());
+                s.push(suggestion.card_shown.map_or('-', CardUtils::char_from_card));
+            }
+        }
+        return s;
+    }
+
+    // Like write_to_string(), but engines that know the same things always
+    // give the same string, no matter what order they learned them in.  This
+    // can still be loaded with load_from_string().  The cards we've shown
+    // other players and the suggestions kept by track_opponents() aren't
+    // included, since they don't change what we know.
+    pub fn write_to_canonical_string(self: &ClueEngine) -> String {
+        let mut s = String::from("");
+        s += &(self.number_of_real_players()).to_string();
+        for player in self.player_data.iter() {
+            s += &player.write_to_canonical_string();
+        }
         return s;
     }
 
@@ -394,6 +929,23 @@ impl ClueEngine {
         for i in 0..(number_of_players+1) {
             clue_engine.load_player_from_string(i as usize, &mut tokenizer)?;
         }
+        while tokenizer.peek() == Some(&'~') {
+            tokenizer.next();
+            let player_index = tokenizer.next_digit().map_err(|_| String::from("Error - couldn't parse player we showed cards to!"))? as usize;
+            while tokenizer.peek().is_some_and(|ch| *ch != '~' && *ch != '|') {
+                let card = CardUtils::card_from_char(tokenizer.next().unwrap())?;
+                clue_engine.record_card_shown(player_index, card)?;
+            }
+        }
+        if tokenizer.peek() == Some(&'|') {
+            tokenizer.next();
+            let my_player_index = tokenizer.next_digit().map_err(|_| String::from("Error - couldn't parse which player we are!"))? as usize;
+            clue_engine.track_opponents(my_player_index)?;
+            while tokenizer.peek().is_some() {
+                let suggestion = clue_engine.load_suggestion_from_string(&mut tokenizer)?;
+                clue_engine.opponent_log.as_mut().unwrap().1.push(suggestion);
+            }
+        }
         // Ensure we've consumed all of the input
         if tokenizer.peek() == None {
             return Ok(clue_engine);
@@ -403,6 +955,38 @@ impl ClueEngine {
         }
     }
 
+    // Like load_from_string(), but for strings we didn't write ourselves:
+    // fails if the engine has any of the problems validate() finds.
+    pub fn load_from_string_and_validate(s: &str) -> Result<ClueEngine, String> {
+        let engine = ClueEngine::load_from_string(s)?;
+        let violations = engine.validate();
+        if !violations.is_empty() {
+            return Err(format!("Invalid session: {}", violations.iter().map(|violation| violation.to_string()).collect::<Vec<String>>().join("; ")));
+        }
+        return Ok(engine);
+    }
+
+    fn load_suggestion_from_string(self: &Self, tokenizer: &mut Tokenizer) -> Result<PublicSuggestion, String> {
+        const SUGGESTION_ENDED_ERROR: &str = "Error - suggestion ended unexpectedly!";
+        let suggesting_player_index = tokenizer.next_digit().map_err(|_| String::from("Error - couldn't parse suggesting player!"))? as usize;
+        let mut cards = [Card::ProfessorPlum; 3];
+        for card in cards.iter_mut() {
+            *card = CardUtils::card_from_char(tokenizer.next().ok_or_else(|| String::from(SUGGESTION_ENDED_ERROR))?)?;
+        }
+        let refuting_player_index = match tokenizer.next().ok_or_else(|| String::from(SUGGESTION_ENDED_ERROR))? {
+            '-' => None,
+            ch => Some(ch.to_digit(10).ok_or_else(|| format!("Error - couldn't parse refuting player '{}'!", ch))? as usize)
+        };
+        let card_shown = match tokenizer.next().ok_or_else(|| String::from(SUGGESTION_ENDED_ERROR))? {
+            '-' => None,
+            ch => Some(CardUtils::card_from_char(ch)?)
+        };
+        if suggesting_player_index >= self.number_of_real_players() || refuting_player_index.is_some_and(|player_index| player_index >= self.number_of_real_players()) {
+            return Err(String::from("Error - player in suggestion out of range!"));
+        }
+        return Ok(PublicSuggestion { suggesting_player_index, cards, refuting_player_index, card_shown });
+    }
+
     // format is (concatenated)
     // <number of cards (or 0 if this is unknown)>
     // one letter per card in has_cards
@@ -448,72 +1032,147 @@ impl ClueEngine {
         Ok(())
     }
 
-    pub fn learn_info_on_card(self: &mut ClueEngine, player_index: usize, card: Card, has_card: bool, update_engine: bool) -> CardSet {
-        let mut changed_cards = HashSet::new();
+    pub fn learn_info_on_card(self: &mut ClueEngine, player_index: usize, card: Card, has_card: bool, update_engine: bool) -> ChangeSet {
+        let mut change_set = ChangeSet::new();
         let update_mode = UpdateEngineMode::from(update_engine);
-        self.learn_info_on_card_internal(player_index, card, has_card, update_mode, &mut changed_cards);
-        return changed_cards;
+        self.learn_info_on_card_internal(player_index, card, has_card, update_mode, &mut change_set);
+        if update_mode == UpdateEngineMode::All && self.deduction_mode == DeductionMode::Complete {
+            self.learn_entailed_facts_internal(&mut change_set);
+        }
+        return change_set;
+    }
+
+    fn learn_info_on_card_internal(self: &mut ClueEngine, player_index: usize, card: Card, has_card: bool, update_engine: UpdateEngineMode, change_set: &mut ChangeSet) {
+        let mut propagation = Propagation::new(update_engine, change_set);
+        propagation.pending.push_back(Fact { player_index, card, has_card });
+        self.propagate(&mut propagation);
+    }
+
+    // Applies pending facts and runs the deduction rules until nothing new is learned.
+    // Every fact is only applied once, so this always finishes.
+    fn propagate(self: &mut ClueEngine, propagation: &mut Propagation) {
+        let mut deduced_facts = vec![];
+        loop {
+            if let Some(fact) = propagation.pending.pop_front() {
+                self.apply_fact(fact, propagation);
+                continue;
+            }
+            if propagation.changes.is_empty() {
+                break;
+            }
+            // Only run the rules once there are no more simple facts to apply.
+            let changes = std::mem::take(&mut propagation.changes);
+            for entry in self.deduction_rules.iter() {
+                if entry.enabled && (propagation.mode == UpdateEngineMode::All || entry.rule.runs_on_minimal_update()) {
+                    entry.rule.deduce(self, &changes, &mut deduced_facts);
+                }
+            }
+            propagation.pending.extend(deduced_facts.drain(..));
+        }
     }
 
-    fn learn_info_on_card_internal(self: &mut ClueEngine, player_index: usize, card: Card, has_card: bool, update_engine: UpdateEngineMode, changed_cards: &mut CardSet) {
+    fn apply_fact(self: &mut ClueEngine, fact: Fact, propagation: &mut Propagation) {
+        let Fact { player_index, card, has_card } = fact;
         {
             let player = &mut self.player_data[player_index];
-            if has_card {
-                player.has_cards.insert(card);
+            let is_new = if has_card { player.has_cards.insert(card) } else { player.not_has_cards.insert(card) };
+            if !is_new {
+                return;
             }
-            else {
-                player.not_has_cards.insert(card);
+            propagation.change_set.add_fact(fact, player.is_solution_player);
+            let clause_changes = if has_card {
+                // Any clause with this card in it is satisfied
+                player.possible_cards.remove_clauses_with_card(card)
             }
-            changed_cards.insert(card);
-            self.examine_clauses(player_index, Some(card), changed_cards);
-        }
-        if update_engine == UpdateEngineMode::All {
-            self.check_solution(Some(card), changed_cards);
+            else {
+                player.possible_cards.remove_card(card)
+            };
+            propagation.change_set.add_clause_changes(player_index, clause_changes);
         }
+        propagation.changes.players.insert(player_index);
+        propagation.changes.cards.insert(card);
 
         if has_card && self.player_data[player_index].is_solution_player {
             // We know we have no other cards in this category.
             for other_card in CardUtils::cards_of_type(CardUtils::card_type(card)) {
                 if other_card != card {
-                    self.learn_info_on_card_internal(player_index, other_card, false, update_engine, changed_cards);
+                    propagation.pending.push_back(Fact { player_index, card: other_card, has_card: false });
                 }
             }
         }
     }
 
-    // Requires that all cards be assigned
-    // Also requires that we've already checked there's no overlap between
-    // has_cards and not_has_cards
-    fn is_consistent_after_all_cards_assigned(self: &mut ClueEngine) -> bool {
-        let mut cards_seen = CardSet::new();
-        for player in self.player_data.iter() {
-            for &card in player.has_cards.iter() {
-                if !cards_seen.insert(card) {
-                    // Already seen this card in someone else's cards, so not consistent
-                    return false;
+    // Learns every fact about who has which card that follows from what we
+    // know, even ones the deduction rules can't figure out.  A player is known
+    // to have (or not have) a card exactly when there's no consistent deal
+    // where that's not true, which we check with a SAT solver.
+    pub fn learn_entailed_facts(self: &mut ClueEngine) -> ChangeSet {
+        let mut change_set = ChangeSet::new();
+        self.learn_entailed_facts_internal(&mut change_set);
+        return change_set;
+    }
+
+    fn learn_entailed_facts_internal(self: &mut ClueEngine, change_set: &mut ChangeSet) {
+        let mut propagation = Propagation::new(UpdateEngineMode::All, change_set);
+        let mut deduced_facts = vec![];
+        self.find_entailed_facts(&mut deduced_facts);
+        propagation.pending.extend(deduced_facts);
+        // Anything the rules figure out from these was already entailed, so
+        // there's no need to look again afterwards.
+        self.propagate(&mut propagation);
+    }
+
+    fn find_entailed_facts(self: &ClueEngine, deduced_facts: &mut Vec<Fact>) {
+        let cnf = ClueCnf::from_engine(self);
+        let mut solver = cnf.to_solver();
+        let model = match solver.solve(&[]) {
+            Some(model) => model,
+            // We're inconsistent, so there's nothing useful to learn.
+            None => return
+        };
+        // Every unknown fact that might be forced, along with the value it
+        // has in every model we've seen so far.
+        let mut candidates = vec![];
+        for (player_index, player) in self.player_data.iter().enumerate() {
+            for card in CardUtils::all_cards() {
+                if player.has_card(card) == None {
+                    let variable = cnf.ownership_variable(player_index, card);
+                    candidates.push((player_index, card, model[variable as usize]));
                 }
             }
-            if player.has_cards.len() != player.num_cards.unwrap() as usize {
-                // wrong number of cards
-                return false;
-            }
-            for clause in player.possible_cards.iter() {
-                if !clause.intersection(&player.has_cards).any(|_| true) {
-                    // This clause is not satisfied
-                    return false;
+        }
+        let mut index = 0;
+        while index < candidates.len() {
+            let (player_index, card, has_card) = candidates[index];
+            let variable = cnf.ownership_variable(player_index, card);
+            match solver.solve(&[if has_card { -variable } else { variable }]) {
+                None => {
+                    deduced_facts.push(Fact { player_index, card, has_card });
+                    index += 1;
+                },
+                Some(model) => {
+                    // This model also shows that any other candidate that has a
+                    // different value in it isn't forced.
+                    candidates.retain(|(other_player_index, other_card, other_has_card)| {
+                        model[cnf.ownership_variable(*other_player_index, *other_card) as usize] == *other_has_card
+                    });
                 }
             }
         }
-        return true;
     }
 
-    pub fn learn_has_one_of_cards(self: &mut ClueEngine, player_index: usize, cards: &CardSet) -> CardSet {
-        let mut changed_cards = HashSet::new();
-        self.learn_has_one_of_cards_internal(player_index, cards, &mut changed_cards);
-        return changed_cards;
+    pub fn learn_has_one_of_cards(self: &mut ClueEngine, player_index: usize, cards: &CardSet) -> ChangeSet {
+        let mut change_set = ChangeSet::new();
+        let mut propagation = Propagation::new(UpdateEngineMode::All, &mut change_set);
+        self.learn_has_one_of_cards_internal(player_index, cards, &mut propagation);
+        self.propagate(&mut propagation);
+        if self.deduction_mode == DeductionMode::Complete {
+            self.learn_entailed_facts_internal(&mut change_set);
+        }
+        return change_set;
     }
 
-    fn learn_has_one_of_cards_internal(self: &mut ClueEngine, player_index: usize, cards: &CardSet, changed_cards: &mut CardSet) {
+    fn learn_has_one_of_cards_internal(self: &mut ClueEngine, player_index: usize, cards: &CardSet, propagation: &mut Propagation) {
         let mut clause_helpful = true;
         let mut new_clause = HashSet::new();
         for card in cards.iter() {
@@ -538,21 +1197,34 @@ impl ClueEngine {
             if new_clause.len() == 1 {
                 // We have learned player has this card!
                 let new_card = *new_clause.iter().next().unwrap();
-                self.learn_info_on_card_internal(player_index, new_card, true, UpdateEngineMode::All, changed_cards);
+                propagation.pending.push_back(Fact { player_index, card: new_card, has_card: true });
             } else {
-                self.player_data[player_index].possible_cards.push(new_clause);
+                // If the player already has a smaller clause, this one
+                // doesn't tell us anything.
+                let clause_changes = self.player_data[player_index].possible_cards.add(new_clause);
+                if !clause_changes.is_empty() {
+                    propagation.change_set.add_clause_changes(player_index, clause_changes);
+                    propagation.changes.players.insert(player_index);
+                }
             }
-            self.examine_clauses(player_index, None, changed_cards);
         }
     }
 
-    pub fn learn_suggest(self: &mut ClueEngine, suggesting_player_index: usize, card1: Card, card2: Card, card3: Card, refuting_player_index: Option<usize>, card_shown: Option<Card>) -> CardSet {
-        let mut changed_cards = HashSet::new();
-        self.learn_suggest_internal(suggesting_player_index, card1, card2, card3, refuting_player_index, card_shown, &mut changed_cards);
-        return changed_cards;
+    pub fn learn_suggest(self: &mut ClueEngine, suggesting_player_index: usize, card1: Card, card2: Card, card3: Card, refuting_player_index: Option<usize>, card_shown: Option<Card>) -> ChangeSet {
+        if let Some((_, suggestions)) = self.opponent_log.as_mut() {
+            suggestions.push(PublicSuggestion { suggesting_player_index, cards: [card1, card2, card3], refuting_player_index, card_shown });
+        }
+        let mut change_set = ChangeSet::new();
+        let mut propagation = Propagation::new(UpdateEngineMode::All, &mut change_set);
+        self.learn_suggest_internal(suggesting_player_index, card1, card2, card3, refuting_player_index, card_shown, &mut propagation);
+        self.propagate(&mut propagation);
+        if self.deduction_mode == DeductionMode::Complete {
+            self.learn_entailed_facts_internal(&mut change_set);
+        }
+        return change_set;
     }
 
-    fn learn_suggest_internal(self: &mut ClueEngine, suggesting_player_index: usize, card1: Card, card2: Card, card3: Card, refuting_player_index: Option<usize>, card_shown: Option<Card>, changed_cards: &mut CardSet) {
+    fn learn_suggest_internal(self: &mut ClueEngine, suggesting_player_index: usize, card1: Card, card2: Card, card3: Card, refuting_player_index: Option<usize>, card_shown: Option<Card>, propagation: &mut Propagation) {
         let mut current_player_index = suggesting_player_index + 1;
         if current_player_index == self.number_of_real_players() as usize {
             current_player_index = 0;
@@ -560,21 +1232,21 @@ impl ClueEngine {
         loop {
             if refuting_player_index == Some(current_player_index) {
                 if let Some(real_card) = card_shown {
-                    self.learn_info_on_card_internal(current_player_index, real_card, true, UpdateEngineMode::All, changed_cards);
+                    propagation.pending.push_back(Fact { player_index: current_player_index, card: real_card, has_card: true });
                 } else {
+                    // Apply what we know so far first, so the clause only has cards we don't know about.
+                    self.propagate(propagation);
                     let possible_cards = HashSet::from_iter(vec![card1, card2, card3].iter().map(|x| *x));
-                    self.learn_has_one_of_cards_internal(current_player_index, &possible_cards, changed_cards);
+                    self.learn_has_one_of_cards_internal(current_player_index, &possible_cards, propagation);
                 }
-                self.check_solution(None, changed_cards);
                 return;
             } else if current_player_index == suggesting_player_index {
                 // No one can refute this.  We're done.
-                self.check_solution(None, changed_cards);
                 return;
             } else {
-                self.learn_info_on_card_internal(current_player_index, card1, false, UpdateEngineMode::Minimal, changed_cards);
-                self.learn_info_on_card_internal(current_player_index, card2, false, UpdateEngineMode::Minimal, changed_cards);
-                self.learn_info_on_card_internal(current_player_index, card3, false, UpdateEngineMode::Minimal, changed_cards);
+                for card in [card1, card2, card3] {
+                    propagation.pending.push_back(Fact { player_index: current_player_index, card, has_card: false });
+                }
                 current_player_index += 1;
                 if current_player_index == self.number_of_real_players() as usize {
                     current_player_index = 0;
@@ -583,71 +1255,108 @@ impl ClueEngine {
         }
     }
 
-    fn examine_clauses(self: &mut ClueEngine, player_index: usize, card: Option<Card>, changed_cards: &mut CardSet) {
-        self.player_data[player_index].eliminate_extraneous_clauses();
-        if let Some(real_card) = card {
-            let player = &mut self.player_data[player_index];
-            // Iterate over all the clauses, but since we might be removing
-            // things from the Vec, keep track of the current index manually.
-            let mut i: usize = 0;
-            while i < player.possible_cards.len() {
-                let clause = &mut player.possible_cards[i];
-                let mut skip_increment = false;
-                if clause.contains(&real_card) {
-                    if player.has_cards.contains(&real_card) {
-                        // We have this card, so this clause is done
-                        player.possible_cards.remove(i);
-                        // adjust loop counter
-                        skip_increment = true;
-                    }
-                    else if player.not_has_cards.contains(&real_card) {
-                        clause.remove(&real_card);
-                        if clause.len() == 1 {
-                            // We have this card!
-                            let have_card = clause.iter().next().unwrap();
-                            player.has_cards.insert(*have_card);
-                            changed_cards.insert(*have_card);
-                            player.possible_cards.remove(i);
-                            // adjust loop counter
-                            skip_increment = true;
-                        }
-                    }
-                }
-                if !skip_increment {
-                    i += 1;
+    // What we would learn from this suggestion, without changing this engine.
+    pub fn what_if_suggest(self: &Self, suggesting_player_index: usize, card1: Card, card2: Card, card3: Card, response: SuggestionResponse) -> WhatIfResult {
+        let mut engine = self.clone();
+        let change_set = engine.learn_suggest(suggesting_player_index, card1, card2, card3, response.refuting_player_index(), response.card_shown());
+        return WhatIfResult::from_change_set(&engine, &change_set);
+    }
+
+    // What we would learn if a player does (or doesn't) have a card, without
+    // changing this engine.
+    pub fn what_if_learn_info_on_card(self: &Self, player_index: usize, card: Card, has_card: bool) -> WhatIfResult {
+        let mut engine = self.clone();
+        let change_set = engine.learn_info_on_card(player_index, card, has_card, true);
+        return WhatIfResult::from_change_set(&engine, &change_set);
+    }
+
+    // Every way this suggestion could be answered that doesn't obviously
+    // contradict what we know.  If see_card_shown is false, we won't get to
+    // see which card is shown (because someone else made the suggestion).
+    pub fn possible_suggestion_responses(self: &Self, suggesting_player_index: usize, card1: Card, card2: Card, card3: Card, see_card_shown: bool) -> Vec<SuggestionResponse> {
+        let mut responses = vec![];
+        let number_of_real_players = self.number_of_real_players();
+        let mut current_player_index = (suggesting_player_index + 1) % number_of_real_players;
+        while current_player_index != suggesting_player_index {
+            let player = &self.player_data[current_player_index];
+            let possible_cards = [card1, card2, card3].iter().copied().filter(|card| player.has_card(*card) != Some(false)).collect::<Vec<Card>>();
+            if see_card_shown {
+                for card in possible_cards.iter() {
+                    responses.push(SuggestionResponse::Refuted { player_index: current_player_index, card_shown: Some(*card) });
                 }
+            } else if !possible_cards.is_empty() {
+                responses.push(SuggestionResponse::Refuted { player_index: current_player_index, card_shown: None });
             }
-        }
-        if let Some(number_of_cards) = self.player_data[player_index].num_cards {
-            if number_of_cards == self.player_data[player_index].has_cards.len() as u8 {
-                // All cards are accounted for.
-                for other_card in CardUtils::all_cards() {
-                    if self.player_data[player_index].has_card(other_card) == None {
-                        self.learn_info_on_card(player_index, other_card, false, true);
-                    }
-                }
-            }
-            else if self.player_data[player_index].has_cards.len() + self.player_data[player_index].possible_cards.len() > (number_of_cards as usize) {
-                // We may be able to figure out something
-                let num_accounted_for = number_of_cards as isize - self.player_data[player_index].has_cards.len() as isize;
-                let card_in_any_clause: &CardSet = &self.player_data[player_index].possible_cards.iter().fold(
-                    HashSet::new(),
-                    |mut set, v| {set.extend(v.iter()); set});
-                for test_card in card_in_any_clause {
-                    // See if we could have this card, by contradiction.
-                    // Assume we don't have this card.  Remove it from
-                    // all clauses.
-                    let new_clauses = Self::remove_card_from_clauses(&self.player_data[player_index].possible_cards, *test_card);
-                    // See if it's possible to satisfy the rest of the clauses with one fewer card.
-                    let is_possible = Self::can_satisfy(&new_clauses, num_accounted_for - 1);
-                    if !is_possible {
-                        // We found a contradiction if we don't have this card,
-                        // so we must have this card.
-                        self.learn_info_on_card_internal(player_index, *test_card, true, UpdateEngineMode::All, changed_cards);
-                    }
-                }
+            // If this player has to refute it, no one after them will.
+            if [card1, card2, card3].iter().any(|card| player.has_card(*card) == Some(true)) {
+                return responses;
             }
+            current_player_index = (current_player_index + 1) % number_of_real_players;
+        }
+        responses.push(SuggestionResponse::NotRefuted);
+        return responses;
+    }
+
+    // what_if_suggest() for every response from possible_suggestion_responses().
+    pub fn what_if_all_suggestion_responses(self: &Self, suggesting_player_index: usize, card1: Card, card2: Card, card3: Card, see_card_shown: bool) -> Vec<(SuggestionResponse, WhatIfResult)> {
+        return self.possible_suggestion_responses(suggesting_player_index, card1, card2, card3, see_card_shown).into_iter()
+            .map(|response| (response, self.what_if_suggest(suggesting_player_index, card1, card2, card3, response)))
+            .collect();
+    }
+
+    // Remembers that we showed this card to a player, so
+    // recommend_card_to_show() can show it to them again.
+    pub fn record_card_shown(self: &mut Self, player_index: usize, card: Card) -> Result<(), String> {
+        if player_index >= self.number_of_real_players() {
+            return Err(format!("Invalid player index {}", player_index));
         }
+        self.cards_shown[player_index].insert(card);
+        return Ok(());
+    }
+
+    pub fn cards_shown_to(self: &Self, player_index: usize) -> &CardSet {
+        return &self.cards_shown[player_index];
+    }
+
+    // Starts keeping every suggestion from now on, so opponent_models() can
+    // work out what each opponent knows.  They're saved with the rest of the
+    // engine by write_to_string().  Suggestions learned before this are
+    // left out, so call this at the start of the game.
+    pub fn track_opponents(self: &mut Self, my_player_index: usize) -> Result<(), String> {
+        // Make sure we'll be able to make the notepads later.
+        OpponentModels::new(self, my_player_index)?;
+        self.opponent_log = Some((my_player_index, vec![]));
+        return Ok(());
+    }
+
+    // The notepads for each opponent, rebuilt from the suggestions since
+    // track_opponents() was called.  None if we're not tracking opponents.
+    pub fn opponent_models(self: &Self) -> Option<OpponentModels> {
+        let (my_player_index, suggestions) = self.opponent_log.as_ref()?;
+        let mut models = OpponentModels::new(self, *my_player_index).ok()?;
+        for suggestion in suggestions {
+            models.learn_public_suggestion(suggestion);
+        }
+        models.update_from_engine(self);
+        return Some(models);
+    }
+
+    // Which card from hand to show when refuting this suggestion, to give
+    // away as little as we can.  A card the suggesting player has already
+    // seen tells them nothing new.  Otherwise, we'd rather show a card other
+    // players have seen, so fewer of our cards are known overall.  Returns
+    // None if we can't refute.
+    pub fn recommend_card_to_show(self: &Self, hand: &CardSet, suggesting_player_index: usize, card1: Card, card2: Card, card3: Card) -> Option<Card> {
+        let mut candidates = [card1, card2, card3].iter().copied().filter(|card| hand.contains(card)).collect::<Vec<Card>>();
+        candidates.sort();
+        candidates.dedup();
+        return candidates.into_iter().min_by_key(|card| {
+            let seen_by_suggester = self.cards_shown.get(suggesting_player_index).is_some_and(|cards| cards.contains(card));
+            let number_of_players_seen = self.cards_shown.iter().filter(|cards| cards.contains(card)).count();
+            // min_by_key() returns the first of the best, so ties go to the
+            // earliest card.
+            (!seen_by_suggester, std::cmp::Reverse(number_of_players_seen))
+        });
     }
 
     pub fn transpose_clauses(possible_cards: &Vec<CardSet>) -> HashMap<Card, HashSet<usize>> {
@@ -681,30 +1390,11 @@ impl ClueEngine {
 
     // Returns whether there's a set of choices that can satisfy all these clauses,
     // given we can only use up to num_accounted_for cards.
+    // The deduction rules use HittingSetCounter directly, so only the tests need this.
+    #[cfg(test)]
     fn can_satisfy(clauses: &Vec<CardSet>, num_unaccounted_for: isize) -> bool {
-        if clauses.len() == 0 {
-            return true;
-        }
-        if num_unaccounted_for <= 0 {
-            return false;
-        }
-        // If there are any empty clauses we have a contradiction already.
-        let smallest_clause = clauses.iter().min_by_key(|x| x.len()).unwrap();
-        if smallest_clause.len() == 0 {
-            return false;
-        }
-        // See if there's any way we can satisfy these
-        // Try one card at a time
-        let card_clauses = ClueEngine::transpose_clauses(clauses);
-        for test_card in smallest_clause {
-            // First, remove all clauses containing this card.
-            let new_clauses = ClueEngine::remove_clauses_with_indices(clauses, card_clauses.get(test_card).unwrap());
-            // See if it's possible to satisfy the rest of the clauses with one fewer card.
-            if ClueEngine::can_satisfy(&new_clauses, num_unaccounted_for - 1) {
-                return true;
-            }
-        }
-        return false;
+        let clause_masks = clauses.iter().map(CardUtils::card_set_to_mask).collect::<Vec<u32>>();
+        return HittingSetCounter::default().can_satisfy(&clause_masks, num_unaccounted_for);
     }
 
     pub fn remove_clauses_with_indices(clauses: &Vec<CardSet>, indices_to_remove: &HashSet<usize>) -> Vec<CardSet> {
@@ -717,246 +1407,183 @@ impl ClueEngine {
         return new_clauses;
     }
 
-    // Check if any cards are the solution, and also if any clauses are in common.
-    fn check_solution(self: &mut Self, card: Option<Card>, changed_cards: &mut CardSet) {
-        if let Some(real_card) = card {
-            self.check_for_all_players_but_one_dont_have_this_card(real_card, changed_cards);
-        }
+    pub fn do_simulation(self: &Self, random_solutions: bool) -> (SimulationData, i32) {
+        let strategy = if random_solutions { SamplingStrategy::RandomSolutions } else { SamplingStrategy::FixedSolutions };
+        return self.do_simulation_with_strategy(strategy);
+    }
 
-        for card_type in CardUtils::all_card_types() {
-            let all_cards = CardUtils::cards_of_type(*card_type).collect::<Vec<Card>>();
-            let mut solution_card: Option<Card> = None;
-            let mut is_solution = true;
-            for test_card in all_cards.iter() {
-                // See if anyone has this card
-                let card_owned = self.player_data.iter().any(|player| player.has_card(*test_card) == Some(true));
-                if !card_owned {
-                    // If there's another possibility, we don't know which is
-                    // right.
-                    if solution_card != None {
-                        solution_card = None;
-                        is_solution = false;
-                    } else {
-                        solution_card = Some(*test_card);
-                    }
-                }
-            }
-            if is_solution && solution_card != None {
-                // There's only one possibility, so this must be it!
-                let solution = solution_card.unwrap();
-                if self.solution_player().has_card(solution) == None {
-                    // also check to make sure we don't have another one in this category
-                    // (if this happened, we're inconsistent already, just move on)
-                    if all_cards.iter().all(|c| !self.solution_player().has_cards.contains(c)) {
-                        self.solution_player_mut().has_cards.insert(solution);
-                        changed_cards.insert(solution);
-                    }
-                }
-            }
-        }
+    pub fn do_simulation_with_strategy(self: &Self, strategy: SamplingStrategy) -> (SimulationData, i32) {
+        return self.do_simulation_with_config(&SimulationConfig::builder().strategy(strategy).build().unwrap());
+    }
 
-        // Finally, see if any people share clauses in common.
-        self.check_for_overlapping_clauses(changed_cards);
+    pub fn do_simulation_with_config(self: &Self, config: &SimulationConfig) -> (SimulationData, i32) {
+        let (fast_simulation_data, total_number_of_simulations) = self.do_simulation_in_thread_pool(config, FastSimulationData::new(self));
+        return (SimulationData::from(&fast_simulation_data), total_number_of_simulations);
     }
 
-    fn check_for_overlapping_clauses(self: &mut Self, changed_cards: &mut CardSet) {
-        let mut clause_hash: HashMap<String, Vec<usize>> = HashMap::new();
-        for idx in 0..self.number_of_real_players() {
-            let player = &self.player_data[idx as usize];
-            for clause in player.possible_cards.iter() {
-                let clause_str = CardUtils::card_set_to_sorted_string(clause);
-                if clause_hash.contains_key(&clause_str) {
-                    clause_hash.get_mut(&clause_str).unwrap().push(idx);
-                }
-                else {
-                    clause_hash.insert(clause_str, vec![idx]);
-                }
+    // Like do_simulation(), but also gathers which cards are owned together,
+    // so you can ask things like how likely a player is to have two cards.
+    pub fn do_simulation_with_joint_data(self: &Self, strategy: SamplingStrategy) -> (SimulationData, JointSimulationData, i32) {
+        let config = SimulationConfig::builder().strategy(strategy).build().unwrap();
+        let (fast_simulation_data, total_number_of_simulations) = self.do_simulation_in_thread_pool(&config, FastSimulationData::new_with_joint_data(self));
+        let joint_simulation_data = fast_simulation_data.joint.clone().unwrap();
+        return (SimulationData::from(&fast_simulation_data), joint_simulation_data, total_number_of_simulations);
+    }
+
+    // Like do_simulation_with_config(), but instead of who has each card,
+    // returns how likely each solution is.  This is meant for restricted
+    // views of the game, like the notepads in OpponentModels.
+    pub fn simulate_solutions(self: &Self, config: &SimulationConfig) -> SolutionDistribution {
+        let (fast_simulation_data, _) = self.do_simulation_in_thread_pool(config, FastSimulationData::new_with_solution_counts(self));
+        return SolutionDistribution::from_counts(fast_simulation_data.solution_counts.unwrap().into_iter().map(|(solution_mask, count)| {
+            let mut solution = [Card::ProfessorPlum; 3];
+            for (solution_card, card) in solution.iter_mut().zip(CardUtils::cards_in_mask(solution_mask)) {
+                *solution_card = card;
             }
+            (solution, count)
+        }));
+    }
+
+    // If there are at most max_deals consistent deals, goes through every one
+    // of them and returns the exact counts along with the number of deals.
+    // Returns None if there are too many (or we can't count them).
+    pub fn enumerate_deals(self: &Self, max_deals: usize) -> Option<(SimulationData, usize)> {
+        let mut fast_simulation_data = FastSimulationData::new(self);
+        let number_of_deals = self.enumerate_deals_internal(max_deals, &mut fast_simulation_data)?;
+        return Some((SimulationData::from(&fast_simulation_data), number_of_deals));
+    }
+
+    fn enumerate_deals_internal(self: &Self, max_deals: usize, fast_simulation_data: &mut FastSimulationData) -> Option<usize> {
+        let mut deal_counter = DealCounter::new(self).ok()?;
+        if deal_counter.total_count() > max_deals as u128 {
+            return None;
         }
-        for (clause, players) in clause_hash.iter() {
-            // If n people all have an n-length clause, no one else can have
-            // a card in that clause.
-            if clause.len() <= players.len() {
-                let affected_people: HashSet<usize> = HashSet::from_iter(players.iter().map(|x| *x));
-                for card in clause.chars().map(|ch| CardUtils::card_from_char(ch).unwrap()) {
-                    changed_cards.insert(card);
-                }
-                for idx in 0..(self.number_of_real_players() + 1) {
-                    if !affected_people.contains(&idx) {
-                        for card in clause.chars().map(|ch| CardUtils::card_from_char(ch).unwrap()) {
-                            if self.player_data[idx as usize].has_card(card) != Some(false) {
-                                self.learn_info_on_card_internal(idx as usize, card, false, UpdateEngineMode::Minimal, changed_cards);
-                            }
-                        }
-                    }
+        deal_counter.for_each_deal(|hands| fast_simulation_data.record_hands(hands));
+        return Some(deal_counter.total_count() as usize);
+    }
+
+    fn do_simulation_in_thread_pool(self: &Self, config: &SimulationConfig, fast_simulation_data: FastSimulationData) -> (FastSimulationData, i32) {
+        if config.parallel() {
+            if let Some(num_threads) = config.num_threads() {
+                // If we can't make the pool, the global one will do.
+                if let Some(thread_pool) = Self::thread_pool_with_threads(num_threads) {
+                    return thread_pool.install(|| self.do_simulation_internal(config, fast_simulation_data));
                 }
             }
         }
+        return self.do_simulation_internal(config, fast_simulation_data);
     }
 
-    fn check_for_all_players_but_one_dont_have_this_card(self: &mut Self, card: Card, changed_cards: &mut CardSet) {
-        let mut someone_has_card = false;
-        let mut number_who_dont_have_card = 0;
-        let mut player_who_might_have_card = None;
-        // - Check also for all cards except one in a category are
-        // accounted for.
-        for i in 0..self.player_data.len() {
-            let player = &mut self.player_data[i];
-            let has_card = player.has_card(card);
-            match has_card {
-                Some(true) => {
-                    // Someone has the card, so the solution is not this.
-                    someone_has_card = true;
-                    break;
-                },
-                Some(false) => {
-                    number_who_dont_have_card += 1;
-                },
-                None => {
-                    // We only look at this if there's only one person who could have this card.
-                    player_who_might_have_card = Some(i);
-                }
-            }
+    // Making a thread pool starts all of its threads, so keep one around for
+    // each number of threads we've been asked for.
+    fn thread_pool_with_threads(num_threads: usize) -> Option<Arc<rayon::ThreadPool>> {
+        static THREAD_POOLS: OnceLock<Mutex<HashMap<usize, Arc<rayon::ThreadPool>>>> = OnceLock::new();
+        let mut thread_pools = THREAD_POOLS.get_or_init(|| Mutex::new(HashMap::new())).lock().ok()?;
+        if let Some(thread_pool) = thread_pools.get(&num_threads) {
+            return Some(thread_pool.clone());
         }
-        if !someone_has_card && number_who_dont_have_card == self.number_of_real_players() {
-            // Every player except one doesn't have this card, so we know the player has it.
-            self.learn_info_on_card_internal(player_who_might_have_card.unwrap(), card, true, UpdateEngineMode::Minimal, changed_cards);
+        let thread_pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(num_threads).build().ok()?);
+        thread_pools.insert(num_threads, thread_pool.clone());
+        return Some(thread_pool);
+    }
+
+    fn do_simulation_internal(self: &Self, config: &SimulationConfig, mut fast_simulation_data: FastSimulationData) -> (FastSimulationData, i32) {
+        let strategy = config.strategy();
+        if strategy == SamplingStrategy::Exhaustive {
+            // Late in the game there may only be a few hundred deals left, and
+            // then it's faster (and exact) to just look at all of them.
+            const MAX_EXHAUSTIVE_DEALS: usize = 100000;
+            if let Some(number_of_deals) = self.enumerate_deals_internal(MAX_EXHAUSTIVE_DEALS, &mut fast_simulation_data) {
+                return (fast_simulation_data, number_of_deals as i32);
+            }
         }
-        else if someone_has_card {
-            // Someone has this card, so no one else does. (including solution)
-            for i in 0..self.player_data.len() {
-                let player = &self.player_data[i];
-                if player.has_card(card) == None {
-                    self.learn_info_on_card_internal(i, card, false, UpdateEngineMode::Minimal, changed_cards);
+        if strategy == SamplingStrategy::ExactUniform || strategy == SamplingStrategy::Exhaustive {
+            if let Ok(mut deal_counter) = DealCounter::new(self) {
+                let num_simulations = config.num_simulations();
+                let mut rng = thread_rng();
+                if deal_counter.total_count() == 0 {
+                    // Nothing is consistent, so there's nothing to count
+                    return (fast_simulation_data, 0);
+                }
+                for _ in 0..num_simulations {
+                    let deal = deal_counter.sample(&mut rng).unwrap();
+                    fast_simulation_data.record_hands(&deal.hands);
                 }
+                return (fast_simulation_data, num_simulations);
             }
+            // Too many clauses (or unknown numbers of cards), so fall
+            // back to the usual way.
         }
-    }
-
-    pub fn do_simulation(self: &Self, random_solutions: bool) -> (SimulationData, i32) {
-        let num_simulations: i32 = if random_solutions {100000} else {20000};
+        let random_solutions = strategy == SamplingStrategy::RandomSolutions;
+        let num_simulations = config.num_simulations();
 
-        const SIMULATION_IN_PARALLEL: bool = true;
-        const NUM_SIMULATIONS_TO_SPLIT: i32 = 1000;
         if self.player_data.iter().any(|player| player.num_cards == None) {
             // Can't do simulations if we don't know how many cards everyone has
-            let mut simulation_data = SimulationData::new();
-            self.initialize_simulation_data(&mut simulation_data);
-            return (simulation_data, 0);
+            return (fast_simulation_data, 0);
         }
-        let mut fast_simulation_data = FastSimulationData::new(self);
         // Find a solution to simulate.
-        // FFV - this iteration could be more generalized
-        let mut solution_possibilities: HashMap<CardType, Vec<Card>> = HashMap::new();
-        let solution_cards = &self.player_data[self.number_of_real_players()].has_cards;
-        let not_solution_cards = &self.player_data[self.number_of_real_players()].not_has_cards;
-        for card_type in CardUtils::all_card_types() {
-            let mut already_found_solution_iter = solution_cards.iter().filter(|&card| CardUtils::card_type(*card) == *card_type);
-            let already_found_solution = already_found_solution_iter.next();
-            if already_found_solution != None {
-                // We know what the solution is for this card already
-                solution_possibilities.insert(*card_type, vec![*(already_found_solution.unwrap())]);
-            }
-            else {
-                // Take all possible cards, except for the ones we know aren't
-                // solutions
-                let all_possible_cards = CardUtils::cards_of_type(*card_type).collect::<HashSet<Card>>();
-                solution_possibilities.insert(*card_type, all_possible_cards.iter().filter_map(|&card| if not_solution_cards.contains(&card) {None } else {Some(card)}).collect());
-            }
-        }
+        let solution_possibilities = self.solution_possibilities();
         let number_of_solutions = solution_possibilities.values().map(|cards| cards.len() as i32).product::<i32>();
         let iterations_per_solution = num_simulations / number_of_solutions;
-        let mut solution_engines: Vec<(ClueEngine, CardSet, i32)> = vec![];
+        // To avoid solution biasing, we need to gather the cards that can be
+        // dealt before we put in the solution.
+        // Otherwise see the test test_simulation_monty_hall_no_player0
+        // In that case, ProfessorPlum only has two possibilities, and once we pick it (or something else)
+        // for the solution it automatically goes to the other player.
+        // But we should be throwing out a lot of those simulations.
+        let base_simulation_state = SimulationState::new(self).unwrap();
+        let mut solution_states: Vec<(SimulationState, i32)> = vec![];
         if random_solutions {
-            let mut available_cards: CardSet = CardUtils::all_cards().collect();
-            for player in self.player_data.iter() {
-                for has_card in player.has_cards.iter() {
-                    available_cards.remove(has_card);
-                }
-            }
+            // Built the first time each solution is picked
+            let mut simulation_states: HashMap<[Card; 3], SimulationState> = HashMap::new();
             let mut rng = thread_rng();
             for _ in 0..num_simulations {
-                let mut engine_copy = self.clone();
-                let mut available_card_vec = available_cards.clone();
                 // TODO - make sure these aren't empty
                 let card1 = solution_possibilities.get(&CardType::Suspect).unwrap().choose(&mut rng).unwrap();
                 let card2 = solution_possibilities.get(&CardType::Weapon).unwrap().choose(&mut rng).unwrap();
                 let card3 = solution_possibilities.get(&CardType::Room).unwrap().choose(&mut rng).unwrap();
-                available_card_vec.remove(card1);
-                available_card_vec.remove(card2);
-                available_card_vec.remove(card3);
-                let available_card_vec = available_card_vec.iter().collect::<Vec<&Card>>();
-                // Call the internal versions to avoid a few allocations
-                let mut ignored_changed_cards = CardSet::new();
-                engine_copy.learn_info_on_card_internal(engine_copy.number_of_real_players(), *card1, true, UpdateEngineMode::All, &mut ignored_changed_cards);
-                engine_copy.learn_info_on_card_internal(engine_copy.number_of_real_players(), *card2, true, UpdateEngineMode::All, &mut ignored_changed_cards);
-                engine_copy.learn_info_on_card_internal(engine_copy.number_of_real_players(), *card3, true, UpdateEngineMode::All, &mut ignored_changed_cards);
-                if ClueEngine::do_one_simulation(&mut engine_copy, &available_card_vec, &mut rng) {
+                let solution = [*card1, *card2, *card3];
+                let simulation_state = simulation_states.entry(solution).or_insert_with(|| base_simulation_state.with_solution(&solution));
+                if simulation_state.deal(&mut rng) {
                     // Results were consistent, so count them
-                    for player_index in 0..engine_copy.player_data.len() {
-                        for card in engine_copy.player_data[player_index].has_cards.iter() {
-                            fast_simulation_data.increment_entry(*card, player_index);
-                        }
-                    }
+                    fast_simulation_data.record_hand_masks(simulation_state.hands());
                 }
             }
-            return (SimulationData::from(&fast_simulation_data), num_simulations);
+            return (fast_simulation_data, num_simulations);
         }
         else {
             for card1 in solution_possibilities.get(&CardType::Suspect).unwrap() {
                 for card2 in solution_possibilities.get(&CardType::Weapon).unwrap() {
                     for card3 in solution_possibilities.get(&CardType::Room).unwrap() {
-                        let mut engine_copy = self.clone();
-                        // To avoid solution biasing, we need to gather the available_cards before we put in the solution.
-                        // Otherwise see the test test_simulation_monty_hall_no_player0
-                        // In that case, ProfessorPlum only has two possibilities, and once we pick it (or something else)
-                        // for the solution it automatically goes to the other player.
-                        // But we should be throwing out a lot of those simulations.
-                        let mut available_cards: CardSet = CardUtils::all_cards().collect();
-                        for player in engine_copy.player_data.iter() {
-                            for has_card in player.has_cards.iter() {
-                                available_cards.remove(has_card);
-                            }
-                        }
-                        available_cards.remove(card1);
-                        available_cards.remove(card2);
-                        available_cards.remove(card3);
-
-                        // Call the internal versions to avoid a few allocations
-                        let mut ignored_changed_cards = CardSet::new();
-                        engine_copy.learn_info_on_card_internal(engine_copy.number_of_real_players(), *card1, true, UpdateEngineMode::All, &mut ignored_changed_cards);
-                        engine_copy.learn_info_on_card_internal(engine_copy.number_of_real_players(), *card2, true, UpdateEngineMode::All, &mut ignored_changed_cards);
-                        engine_copy.learn_info_on_card_internal(engine_copy.number_of_real_players(), *card3, true, UpdateEngineMode::All, &mut ignored_changed_cards);
-                        if SIMULATION_IN_PARALLEL {
+                        let simulation_state = base_simulation_state.with_solution(&[*card1, *card2, *card3]);
+                        if config.parallel() {
                             // Don't split on just cards, because if there are only a few solution possibilities
                             // we won't get good parallelism.
                             let mut temp_iterations_per_solution = iterations_per_solution;
                             while temp_iterations_per_solution > 0 {
-                                solution_engines.push((engine_copy.clone(), available_cards.clone(), min(NUM_SIMULATIONS_TO_SPLIT, temp_iterations_per_solution)));
-                                temp_iterations_per_solution -= NUM_SIMULATIONS_TO_SPLIT;
+                                solution_states.push((simulation_state.clone(), min(config.chunk_size(), temp_iterations_per_solution)));
+                                temp_iterations_per_solution -= config.chunk_size();
                             }
                         }
                         else {
-                            solution_engines.push((engine_copy, available_cards, iterations_per_solution));
+                            solution_states.push((simulation_state, iterations_per_solution));
                         }
                     }
                 }
             }
 
-            let simulations_per_iteration: i32 = solution_engines.iter().map(|data| data.2).sum();
+            let simulations_per_iteration: i32 = solution_states.iter().map(|data| data.1).sum();
             let total_number_of_simulations;
-            if SIMULATION_IN_PARALLEL {
+            if config.parallel() {
                 let mut iterations = 0;
-                const MAX_ITERATIONS: i32 = 100;
-                while iterations < MAX_ITERATIONS && fast_simulation_data.num_simulations() < 1000 {
+                while iterations < config.max_iterations() && fast_simulation_data.num_simulations() < config.min_accepted_simulations() {
                     iterations += 1;
-                    let results: Vec<FastSimulationData> = solution_engines.par_iter().map(|solution_data| {
-                        let mut local_simulation_data = FastSimulationData::new(self);
-                        
-                        let engine = &solution_data.0;
-                        let available_cards = &solution_data.1;
-                        let iterations = solution_data.2;
-                        Self::gather_simulation_data(&mut local_simulation_data, &engine, available_cards, iterations);
+                    let results: Vec<FastSimulationData> = solution_states.par_iter_mut().map(|solution_data| {
+                        let mut local_simulation_data = fast_simulation_data.new_like();
+
+                        let simulation_state = &mut solution_data.0;
+                        let iterations = solution_data.1;
+                        Self::gather_simulation_data(&mut local_simulation_data, simulation_state, iterations);
                         local_simulation_data
                     }).collect();
                     for result in results {
@@ -967,78 +1594,66 @@ impl ClueEngine {
             }
             else {
                 let mut iterations = 0;
-                const MAX_ITERATIONS: i32 = 100;
-                while iterations < MAX_ITERATIONS && fast_simulation_data.num_simulations() < 1000 {
+                while iterations < config.max_iterations() && fast_simulation_data.num_simulations() < config.min_accepted_simulations() {
                     iterations += 1;
-                    for (engine, available_cards, iterations) in &solution_engines {
-                        Self::gather_simulation_data(&mut fast_simulation_data, &engine, &available_cards, *iterations);
+                    for (simulation_state, iterations) in solution_states.iter_mut() {
+                        Self::gather_simulation_data(&mut fast_simulation_data, simulation_state, *iterations);
                     }
                 }
                 total_number_of_simulations = iterations * simulations_per_iteration;
             }
 
-            return (SimulationData::from(&fast_simulation_data), total_number_of_simulations);
-        }
-    }
-
-    // Note that we do at least 20,000 of these, so performance is very important!
-    fn gather_simulation_data(simulation_data: &mut FastSimulationData, engine: &ClueEngine, available_cards: &CardSet, iterations: i32) {
-        let available_card_vec = available_cards.iter().collect::<Vec<&Card>>();
-        let mut rng = thread_rng();
-        for _ in 0..iterations {
-            let mut temp_engine = engine.clone();
-            if ClueEngine::do_one_simulation(&mut temp_engine, &available_card_vec, &mut rng) {
-                // Results were consistent, so count them
-                for player_index in 0..temp_engine.player_data.len() {
-                    for card in temp_engine.player_data[player_index].has_cards.iter() {
-                        simulation_data.increment_entry(*card, player_index);
-                    }
-                }
-            }
+            return (fast_simulation_data, total_number_of_simulations);
         }
     }
 
-    // Returns whether the simulation is consistent
-    // Note that we do at least 20,000 of these, so performance is very important!
-    fn do_one_simulation(engine: &mut ClueEngine, available_cards: &Vec<&Card>, rng: &mut ThreadRng) -> bool {
-        let mut temp_available_cards = available_cards.clone();
-        temp_available_cards.shuffle(rng);
-        let num_available_cards = temp_available_cards.len();
-        // Assign all values randomly.  For performance, do minimal checking while doing this, then check
-        // everything at the end.
-        let mut index = 0;
-        for player_index in 0..engine.number_of_real_players() {
-            let player = &engine.player_data[player_index];
-            let num_cards_needed = player.num_cards.unwrap() as usize - player.has_cards.len();
-            // If there are not enough cards available, we're
-            // inconsistent.
-            if num_available_cards - index < num_cards_needed {
-                return false;
+    // The cards that could still be the solution, by category.
+    // FFV - this iteration could be more generalized
+    pub(crate) fn solution_possibilities(self: &Self) -> HashMap<CardType, Vec<Card>> {
+        let mut solution_possibilities: HashMap<CardType, Vec<Card>> = HashMap::new();
+        let solution_cards = &self.solution_player().has_cards;
+        let not_solution_cards = &self.solution_player().not_has_cards;
+        for card_type in CardUtils::all_card_types() {
+            let mut already_found_solution_iter = solution_cards.iter().filter(|&card| CardUtils::card_type(*card) == *card_type);
+            let already_found_solution = already_found_solution_iter.next();
+            if already_found_solution != None {
+                // We know what the solution is for this card already
+                solution_possibilities.insert(*card_type, vec![*(already_found_solution.unwrap())]);
             }
             else {
-                for _ in 0..num_cards_needed {
-                    let card_to_add = temp_available_cards[index];
-                    index += 1;
-                    // see if we're going to be inconsistent and exit early
-                    // This early return helps performance when we have to do a lot of simulations
-                    // because there are a lot of conditions to meet
-                    if engine.player_data[player_index].not_has_cards.contains(card_to_add) {
-                        return false;
-                    }
-                    // This used to be a call to learn_info_on_card_internal(), but for performance
-                    // just do the core operation here.
-                    engine.player_data[player_index].has_cards.insert(*card_to_add);
-                }
+                // Take all possible cards, except for the ones we know aren't
+                // solutions
+                let all_possible_cards = CardUtils::cards_of_type(*card_type).collect::<HashSet<Card>>();
+                solution_possibilities.insert(*card_type, all_possible_cards.iter().filter_map(|&card| if not_solution_cards.contains(&card) {None } else {Some(card)}).collect());
             }
         }
-        // All players assigned.  Check consistency.
-        return engine.is_consistent_after_all_cards_assigned();
+        return solution_possibilities;
+    }
+
+    // Returns an iterator over up to n randomly dealt hands that are consistent with
+    // everything we know.  Each deal has every player's hand and then the solution.
+    // The solution is picked at random first (like do_simulation(true)), so this
+    // isn't exactly uniform over all consistent deals.
+    pub fn sample_deals<R: Rng>(self: &Self, n: usize, rng: R) -> DealSampler<'_, R> {
+        let solution_possibilities = self.solution_possibilities();
+        DealSampler {
+            engine: self,
+            rng,
+            remaining: n,
+            solution_possibilities: CardUtils::all_card_types().map(|card_type| solution_possibilities.get(card_type).unwrap().clone()).collect(),
+            simulation_state: SimulationState::new(self),
+            solution_simulation_states: HashMap::new(),
+        }
     }
 
-    fn initialize_simulation_data(self: &Self, data: &mut SimulationData) {
-        for card in CardUtils::all_cards() {
-            let zeros = (0..(self.player_data.len())).map(|_| 0).collect();
-            data.insert(card, zeros);
+    // Note that we do at least 20,000 of these, so performance is very important!
+    fn gather_simulation_data(simulation_data: &mut FastSimulationData, simulation_state: &mut SimulationState, iterations: i32) {
+        let mut rng = thread_rng();
+        for _ in 0..iterations {
+            if simulation_state.deal(&mut rng) {
+                // Results were consistent, so count them
+                simulation_data.record_hand_masks(simulation_state.hands());
+            }
         }
     }
 
@@ -1047,6 +1662,14 @@ impl ClueEngine {
              !player.has_cards.intersection(&player.not_has_cards).any(|_| true));
     }
 
+    // Everything about what we know that can't be true, like a card two
+    // players have or hands that don't add up to the deck.  This is empty if
+    // nothing is wrong.  It's much more thorough than is_consistent(), but
+    // it still doesn't look for contradictions that take reasoning to find.
+    pub fn validate(self: &Self) -> Vec<Violation> {
+        return validation::find_violations(self);
+    }
+
     pub fn who_has_card(self: &Self, card: Card) -> HashSet<usize> {
         let mut possible_owners = HashSet::new();
         for i in 0..(self.number_of_real_players() + 1) {
@@ -1154,6 +1777,16 @@ mod tests {
         assert_eq!("CLU", CardUtils::card_set_to_sorted_string(&vec![Card::BilliardRoom, Card::Wrench, Card::MrGreen].into_iter().collect()));
     }
 
+    #[test]
+    #[allow(deprecated)]
+    fn test_eliminate_extraneous_clauses_does_nothing() {
+        let mut engine = ClueEngine::load_from_string("63-.3--AB-ABC-CD.3-.3-.3-.3-.3-.").unwrap();
+        let clauses = engine.player_data[1].possible_cards.clone();
+        engine.player_data[1].eliminate_extraneous_clauses();
+        assert_eq!(clauses, engine.player_data[1].possible_cards);
+        assert_eq!(vec![make_card_set(vec![Card::ProfessorPlum, Card::ColonelMustard]), make_card_set(vec![Card::MrGreen, Card::MissScarlet])], engine.player_data[1].possible_cards);
+    }
+
     #[test]
     fn test_eliminate_extraneous_clauses_empty() {
         let mut clauses: Vec<CardSet> = vec![];
@@ -1345,6 +1978,19 @@ mod tests {
         assert_eq!(false, ClueEngine::can_satisfy(&clauses, 2));
     }
 
+    #[test]
+    fn test_hitting_set_counter_min_cards() {
+        let mut counter = HittingSetCounter::default();
+        // ABC, ADE, BD, F: A or B or D can do two clauses, but not three
+        let clauses = [0b111, 0b11001, 0b1010, 0b100000];
+        assert_eq!(Some(3), counter.min_cards(&clauses));
+        assert_eq!(Some(2), counter.min_cards(&clauses[..3]));
+        assert_eq!(Some(0), counter.min_cards(&[]));
+        assert_eq!(None, counter.min_cards(&[0b1, 0]));
+        assert!(counter.can_satisfy(&clauses, 3));
+        assert!(!counter.can_satisfy(&clauses, 2));
+    }
+
     #[test]
     fn test_remove_clauses_with_indices_empty() {
         let clauses: Vec<CardSet> = vec![
@@ -1416,4 +2062,59 @@ mod tests {
         assert_eq!(target.get_card_data(Card::MrGreen), &vec![11 as usize,13,12][..]);
         assert_eq!(target.get_card_data(Card::MissScarlet), &vec![9 as usize,7,3][..]);
     }
+
+    fn make_dealt_engine(player_0_cards: Vec<Card>, solution_cards: Vec<Card>) -> ClueEngine {
+        let mut engine = ClueEngine::new(2, None).unwrap();
+        engine.player_data[0].has_cards = make_card_set(player_0_cards.clone());
+        engine.player_data[2].has_cards = make_card_set(solution_cards.clone());
+        engine.player_data[1].has_cards = CardUtils::all_cards().filter(|card| !player_0_cards.contains(card) && !solution_cards.contains(card)).collect();
+        return engine;
+    }
+
+    #[test]
+    fn test_joint_simulation_data_record_deals() {
+        let mut joint = JointSimulationData::new(3);
+        let solution = vec![Card::ProfessorPlum, Card::Knife, Card::Hall];
+        let first = make_dealt_engine(vec![Card::ColonelMustard, Card::Rope, Card::Study, Card::Lounge, Card::MrGreen, Card::Wrench, Card::Library, Card::Ballroom, Card::Kitchen], solution.clone());
+        let second = make_dealt_engine(vec![Card::MissScarlet, Card::Candlestick, Card::Conservatory, Card::DiningRoom, Card::MrGreen, Card::Wrench, Card::Library, Card::Ballroom, Card::Kitchen], solution.clone());
+        joint.record_hands(first.player_data.iter().map(|player| &player.has_cards));
+        joint.record_hands(first.player_data.iter().map(|player| &player.has_cards));
+        joint.record_hands(second.player_data.iter().map(|player| &player.has_cards));
+
+        assert_eq!(3, joint.num_simulations());
+        assert_eq!(2, joint.count_both(Card::Rope, 0, Card::Study, 0));
+        assert_eq!(1, joint.count_both(Card::Rope, 1, Card::Study, 1));
+        assert_eq!(0, joint.count_both(Card::Rope, 0, Card::Study, 1));
+        assert_eq!(3, joint.count_both(Card::Hall, 2, Card::MrGreen, 0));
+        // Rope and Study always move together
+        assert!((joint.correlation(Card::Rope, 0, Card::Study, 0).unwrap() - 1.0).abs() < 1e-9);
+        assert!((joint.correlation(Card::Rope, 0, Card::Candlestick, 0).unwrap() + 1.0).abs() < 1e-9);
+        // The solution never changes, so there's nothing to correlate
+        assert_eq!(None, joint.correlation(Card::Hall, 2, Card::Rope, 0));
+
+        let hands = joint.hand_distribution(0);
+        assert_eq!(2, hands.len());
+        assert_eq!(first.player_data[0].has_cards, hands[0].0);
+        assert_eq!(2, hands[0].1);
+        assert_eq!(vec![(make_card_set(solution), 3)], joint.hand_distribution(2));
+    }
+
+    #[test]
+    fn test_joint_simulation_data_accumulate() {
+        let engine = ClueEngine::new(2, None).unwrap();
+        let dealt_engine = make_dealt_engine(vec![Card::ColonelMustard, Card::Rope, Card::Study, Card::Lounge, Card::MrGreen, Card::Wrench, Card::Library, Card::Ballroom, Card::Kitchen], vec![Card::ProfessorPlum, Card::Knife, Card::Hall]);
+        let mut target = FastSimulationData::new_with_joint_data(&engine);
+        let mut source = target.new_like();
+        let hand_masks = dealt_engine.player_data.iter().map(|player| CardUtils::card_set_to_mask(&player.has_cards)).collect::<Vec<u32>>();
+        target.record_hand_masks(&hand_masks);
+        source.record_hand_masks(&hand_masks);
+
+        target.accumulate_from(&source);
+
+        assert_eq!(2, target.num_simulations());
+        let joint = target.joint.unwrap();
+        assert_eq!(2, joint.num_simulations());
+        assert_eq!(2, joint.count_both(Card::Rope, 0, Card::Hall, 2));
+        assert_eq!(1, joint.hand_distribution(1).len());
+    }
 }
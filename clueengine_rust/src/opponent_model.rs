@@ -0,0 +1,253 @@
+// Keeps track of what each opponent could know, so we can tell how close
+// they are to solving the case.
+//
+// Each opponent gets their own notepad, which is a ClueEngine that only
+// learns what that opponent has seen: who refuted each suggestion (which
+// everyone sees), the cards shown to them when they made a suggestion, and
+// what we know about their own hand.  When another player shows an opponent
+// a card we don't get to see, their notepad only learns that it was one of
+// the suggested cards, so the notepads can know less than the opponents
+// really do, but never more.
+//
+// To keep the notepads with a saved game, use ClueEngine::track_opponents()
+// and ClueEngine::opponent_models() instead of making an OpponentModels.
+
+use crate::{Card, CardUtils, ClueEngine, SimulationConfig, SolutionDistribution};
+
+// How sure an opponent could be about the solution.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Threat {
+    pub player_index: usize,
+    // How uncertain they are, in bits.  0 means they know the solution.
+    pub entropy: f64,
+    // The accusation they'd most likely make
+    pub best_accusation: [Card; 3],
+    // The chance that accusation is right
+    pub best_accusation_probability: f64,
+}
+
+impl Threat {
+    // Whether they'd probably be right if they accused now.
+    pub fn likely_to_accuse(self: &Self, threshold: f64) -> bool {
+        return self.best_accusation_probability >= threshold;
+    }
+}
+
+// A suggestion as everyone at the table saw it, along with the card the
+// suggesting player was shown if we saw it too.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PublicSuggestion {
+    pub suggesting_player_index: usize,
+    pub cards: [Card; 3],
+    pub refuting_player_index: Option<usize>,
+    pub card_shown: Option<Card>,
+}
+
+#[derive(Clone, Debug)]
+pub struct OpponentModels {
+    my_player_index: usize,
+    // Indexed by player; None for us.
+    notepads: Vec<Option<ClueEngine>>,
+}
+
+impl OpponentModels {
+    // engine is our own engine, which is only used to find out how many
+    // players there are and how many cards they have.
+    pub fn new(engine: &ClueEngine, my_player_index: usize) -> Result<OpponentModels, String> {
+        let number_of_real_players = engine.number_of_real_players();
+        if my_player_index >= number_of_real_players {
+            return Err(format!("Invalid player index {}", my_player_index));
+        }
+        let cards_per_player = engine.player_data[..number_of_real_players].iter().map(|player| player.num_cards.ok_or_else(|| String::from("Need to know how many cards each player has"))).collect::<Result<Vec<u8>, String>>()?;
+        let mut notepads = vec![];
+        for player_index in 0..number_of_real_players {
+            notepads.push(if player_index == my_player_index { None } else { Some(ClueEngine::new(number_of_real_players as u8, Some(&cards_per_player))?) });
+        }
+        return Ok(OpponentModels { my_player_index, notepads });
+    }
+
+    pub fn my_player_index(self: &Self) -> usize {
+        return self.my_player_index;
+    }
+
+    // The opponents, in order.
+    pub fn opponents(self: &Self) -> impl Iterator<Item=usize> + '_ {
+        return (0..self.notepads.len()).filter(move |player_index| *player_index != self.my_player_index);
+    }
+
+    // What this opponent could know, or None if this is us.
+    pub fn notepad(self: &Self, player_index: usize) -> Option<&ClueEngine> {
+        return self.notepads.get(player_index).and_then(|notepad| notepad.as_ref());
+    }
+
+    // Takes the same arguments as ClueEngine::learn_suggest().  card_shown
+    // is the card the suggesting player saw, if we know it (because we made
+    // the suggestion or showed the card).  Only the suggesting player's
+    // notepad learns which card it was.
+    pub fn learn_suggest(self: &mut Self, suggesting_player_index: usize, card1: Card, card2: Card, card3: Card, refuting_player_index: Option<usize>, card_shown: Option<Card>) {
+        for (player_index, notepad) in self.notepads.iter_mut().enumerate() {
+            if let Some(notepad) = notepad {
+                let card_seen = if player_index == suggesting_player_index { card_shown } else { None };
+                notepad.learn_suggest(suggesting_player_index, card1, card2, card3, refuting_player_index, card_seen);
+            }
+        }
+    }
+
+    pub fn learn_public_suggestion(self: &mut Self, suggestion: &PublicSuggestion) {
+        let [card1, card2, card3] = suggestion.cards;
+        self.learn_suggest(suggestion.suggesting_player_index, card1, card2, card3, suggestion.refuting_player_index, suggestion.card_shown);
+    }
+
+    // Teaches each opponent what we know about their own hand (they know
+    // at least that much), and the cards we've shown them (see
+    // ClueEngine::record_card_shown()).
+    pub fn update_from_engine(self: &mut Self, engine: &ClueEngine) {
+        let my_player_index = self.my_player_index;
+        for (player_index, notepad) in self.notepads.iter_mut().enumerate() {
+            if let Some(notepad) = notepad {
+                let player = &engine.player_data[player_index];
+                for card in player.has_cards.iter() {
+                    if notepad.player_data[player_index].has_card(*card) == None {
+                        notepad.learn_info_on_card(player_index, *card, true, true);
+                    }
+                }
+                for card in player.not_has_cards.iter() {
+                    if notepad.player_data[player_index].has_card(*card) == None {
+                        notepad.learn_info_on_card(player_index, *card, false, true);
+                    }
+                }
+                for card in engine.cards_shown_to(player_index).iter() {
+                    if notepad.player_data[my_player_index].has_card(*card) == None {
+                        notepad.learn_info_on_card(my_player_index, *card, true, true);
+                    }
+                }
+            }
+        }
+    }
+
+    // How many solutions are still possible as far as this opponent knows.
+    pub fn remaining_solutions(self: &Self, player_index: usize) -> Option<usize> {
+        let notepad = self.notepad(player_index)?;
+        let solution_possibilities = notepad.solution_possibilities();
+        return Some(CardUtils::all_card_types().map(|card_type| solution_possibilities[card_type].len()).product());
+    }
+
+    // How close this opponent is to solving, from 0 (knows nothing) to 1
+    // (knows the solution), measured by how much they've narrowed down the
+    // possible solutions on a log scale.
+    pub fn progress(self: &Self, player_index: usize) -> Option<f64> {
+        let remaining_solutions = self.remaining_solutions(player_index)?;
+        let all_solutions = CardUtils::all_card_types().map(|card_type| CardUtils::cards_of_type(*card_type).count()).product::<usize>();
+        if remaining_solutions == 0 {
+            // The notepad is inconsistent, which can't really happen.
+            return Some(1.0);
+        }
+        return Some(1.0 - (remaining_solutions as f64).ln() / (all_solutions as f64).ln());
+    }
+
+    // The opponents, closest to solving first.
+    pub fn opponents_by_progress(self: &Self) -> Vec<(usize, f64)> {
+        let mut opponents = self.opponents().map(|player_index| (player_index, self.progress(player_index).unwrap())).collect::<Vec<(usize, f64)>>();
+        opponents.sort_by(|(_, progress1), (_, progress2)| progress2.partial_cmp(progress1).unwrap());
+        return opponents;
+    }
+
+    // How likely each solution looks to this opponent, simulated from their
+    // notepad.  Since the notepad doesn't have everything they know about
+    // their own hand (or cards other players showed them), they may really
+    // be more sure than this.
+    pub fn solution_distribution(self: &Self, player_index: usize, config: &SimulationConfig) -> Option<SolutionDistribution> {
+        return Some(self.notepad(player_index)?.simulate_solutions(config));
+    }
+
+    // Returns None for us, or if there were no consistent deals.
+    pub fn threat(self: &Self, player_index: usize, config: &SimulationConfig) -> Option<Threat> {
+        let distribution = self.solution_distribution(player_index, config)?;
+        let (best_accusation, best_accusation_probability) = distribution.most_likely()?;
+        return Some(Threat { player_index, entropy: distribution.entropy(), best_accusation, best_accusation_probability });
+    }
+
+    // Every opponent's threat, most likely to accuse correctly first.
+    pub fn threats(self: &Self, config: &SimulationConfig) -> Vec<Threat> {
+        let mut threats = self.opponents().filter_map(|player_index| self.threat(player_index, config)).collect::<Vec<Threat>>();
+        threats.sort_by(|threat1, threat2| threat2.best_accusation_probability.partial_cmp(&threat1.best_accusation_probability).unwrap());
+        return threats;
+    }
+
+    // The cards this opponent knows are the solution.
+    pub fn known_solution_cards(self: &Self, player_index: usize) -> Option<Vec<Card>> {
+        let notepad = self.notepad(player_index)?;
+        // In the same order as CardUtils::all_card_types()
+        let cards = CardUtils::all_card_types().filter_map(|card_type| {
+            let possibilities = CardUtils::cards_of_type(*card_type).filter(|card| notepad.solution_player().has_card(*card) != Some(false)).collect::<Vec<Card>>();
+            if possibilities.len() == 1 { Some(possibilities[0]) } else { None }
+        }).collect::<Vec<Card>>();
+        return Some(cards);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opponent_model::*;
+    use crate::SamplingStrategy;
+
+    #[test]
+    fn test_only_suggester_sees_card() {
+        let engine = ClueEngine::new(4, None).unwrap();
+        let mut models = OpponentModels::new(&engine, 0).unwrap();
+        assert!(models.notepad(0).is_none());
+        assert_eq!(vec![1, 2, 3], models.opponents().collect::<Vec<usize>>());
+
+        // Player 1 suggests and we show them the knife.
+        models.learn_suggest(1, Card::ProfessorPlum, Card::Knife, Card::Hall, Some(0), Some(Card::Knife));
+        assert_eq!(Some(true), models.notepad(1).unwrap().player_data[0].has_card(Card::Knife));
+        assert_eq!(None, models.notepad(2).unwrap().player_data[0].has_card(Card::Knife));
+        assert_eq!(1, models.notepad(2).unwrap().player_data[0].possible_cards.len());
+        // Everyone sees that players 2 and 3 couldn't refute
+        assert_eq!(Some(false), models.notepad(3).unwrap().player_data[2].has_card(Card::Knife));
+    }
+
+    #[test]
+    fn test_threats() {
+        let mut engine = ClueEngine::new(3, None).unwrap();
+        for card in [Card::ProfessorPlum, Card::Knife, Card::Hall] {
+            engine.learn_info_on_card(2, card, false, true);
+        }
+        let mut models = OpponentModels::new(&engine, 0).unwrap();
+        // Only player 2 knows who has the Knife
+        models.learn_suggest(2, Card::ProfessorPlum, Card::Knife, Card::Hall, None, None);
+        models.update_from_engine(&engine);
+        let config = SimulationConfig::builder().strategy(SamplingStrategy::ExactUniform).num_simulations(2000).parallel(false).build().unwrap();
+        let threats = models.threats(&config);
+        assert_eq!(2, threats.len());
+        assert_eq!(2, threats[0].player_index);
+        assert_eq!([Card::ProfessorPlum, Card::Knife, Card::Hall], threats[0].best_accusation);
+        assert_eq!(1.0, threats[0].best_accusation_probability);
+        assert!(threats[0].entropy.abs() < 1e-12);
+        assert!(threats[0].likely_to_accuse(0.9));
+        assert!(threats[1].entropy > 5.0);
+        assert!(!threats[1].likely_to_accuse(0.9));
+        assert_eq!(None, models.threat(0, &config));
+    }
+
+    #[test]
+    fn test_progress() {
+        let engine = ClueEngine::new(3, None).unwrap();
+        let mut models = OpponentModels::new(&engine, 0).unwrap();
+        assert_eq!(Some(324), models.remaining_solutions(1));
+        assert_eq!(Some(0.0), models.progress(1));
+        assert_eq!(None, models.progress(0));
+
+        // No one could refute player 2, and they don't have the cards themselves.
+        let mut engine = engine;
+        for card in [Card::ProfessorPlum, Card::Knife, Card::Hall] {
+            engine.learn_info_on_card(2, card, false, true);
+        }
+        models.learn_suggest(2, Card::ProfessorPlum, Card::Knife, Card::Hall, None, None);
+        models.update_from_engine(&engine);
+        assert_eq!(Some(1), models.remaining_solutions(2));
+        assert_eq!(Some(1.0), models.progress(2));
+        assert_eq!(Some(vec![Card::ProfessorPlum, Card::Knife, Card::Hall]), models.known_solution_cards(2));
+        assert_eq!(vec![(2, 1.0), (1, 0.0)], models.opponents_by_progress().into_iter().map(|(player_index, progress)| (player_index, (progress * 1000.0).round() / 1000.0)).collect::<Vec<(usize, f64)>>());
+    }
+}
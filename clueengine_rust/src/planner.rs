@@ -0,0 +1,284 @@
+// Decides where to move on the board, weighing how much we'd learn from the
+// suggestions we could make in each room against how long it takes to get
+// there.
+//
+// How much a suggestion tells us is estimated from deals sampled from what
+// the engine knows: it's how much, on average, seeing the response would
+// narrow down the solution (the mutual information between the response and
+// the solution, in bits).  When the refuting player could show more than one
+// card, each one is assumed equally likely.
+//
+// Moving is planned a few turns ahead.  A position is worth the information
+// from the next suggestion we'll get to make, multiplied by discount for
+// every turn we have to wait for it, assuming we keep making the best move
+// after each roll.
+
+use crate::board::{Board, BoardPosition};
+use crate::{Card, CardType, CardUtils, ClueEngine, Deal, DealCounter};
+use rand::Rng;
+use std::collections::HashMap;
+
+// The best suggestion we can make in a room.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct RoomSuggestion {
+    pub room: Card,
+    pub suspect: Card,
+    pub weapon: Card,
+    // In bits
+    pub expected_information: f64,
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum TurnChoice {
+    // Take the secret passage to this room instead of rolling.
+    SecretPassage(Card),
+    Roll,
+}
+
+// The refuting player and the card they showed, or None if no one refuted.
+type Response = Option<(usize, Card)>;
+
+#[derive(Clone, Debug)]
+pub struct MovementPlanner {
+    board: Board,
+    // Indexed in the same order as CardUtils::cards_of_type(CardType::Room)
+    room_suggestions: Vec<RoomSuggestion>,
+    // How much less a suggestion is worth for each turn we have to wait for it.
+    pub discount: f64,
+}
+
+impl MovementPlanner {
+    pub const DEFAULT_DISCOUNT: f64 = 0.8;
+
+    // Samples num_deals deals from engine to estimate how useful each
+    // suggestion by player_index would be.
+    pub fn new<R: Rng>(engine: &ClueEngine, board: Board, player_index: usize, num_deals: usize, mut rng: R) -> Result<MovementPlanner, String> {
+        // Sample uniformly if we can, so the estimates aren't biased.
+        let deals = match DealCounter::new(engine) {
+            Ok(mut deal_counter) => (0..num_deals).filter_map(|_| deal_counter.sample(&mut rng)).collect::<Vec<Deal>>(),
+            Err(_) => engine.sample_deals(num_deals, rng).collect::<Vec<Deal>>()
+        };
+        return MovementPlanner::from_deals(engine, board, player_index, &deals);
+    }
+
+    pub fn from_deals(engine: &ClueEngine, board: Board, player_index: usize, deals: &[Deal]) -> Result<MovementPlanner, String> {
+        if deals.is_empty() {
+            return Err(String::from("No consistent deals to plan with"));
+        }
+        if player_index >= engine.number_of_real_players() {
+            return Err(format!("Invalid player index {}", player_index));
+        }
+        let room_suggestions = CardUtils::cards_of_type(CardType::Room).map(|room| {
+            let mut best = RoomSuggestion { room, suspect: Card::ProfessorPlum, weapon: Card::Knife, expected_information: -1.0 };
+            for suspect in CardUtils::cards_of_type(CardType::Suspect) {
+                for weapon in CardUtils::cards_of_type(CardType::Weapon) {
+                    let expected_information = MovementPlanner::suggestion_information(deals, player_index, [suspect, weapon, room]);
+                    if expected_information > best.expected_information {
+                        best = RoomSuggestion { room, suspect, weapon, expected_information };
+                    }
+                }
+            }
+            best
+        }).collect();
+        return Ok(MovementPlanner { board, room_suggestions, discount: MovementPlanner::DEFAULT_DISCOUNT });
+    }
+
+    // The mutual information between the solution and the response we'd see
+    // to this suggestion.
+    fn suggestion_information(deals: &[Deal], player_index: usize, cards: [Card; 3]) -> f64 {
+        let number_of_real_players = deals[0].hands.len() - 1;
+        let mut joint_weights: HashMap<([Card; 3], Response), f64> = HashMap::new();
+        let mut solution_weights: HashMap<[Card; 3], f64> = HashMap::new();
+        let mut response_weights: HashMap<Response, f64> = HashMap::new();
+        for deal in deals.iter() {
+            let mut solution = deal.solution().iter().copied().collect::<Vec<Card>>();
+            solution.sort();
+            let solution = [solution[0], solution[1], solution[2]];
+            *solution_weights.entry(solution).or_insert(0.0) += 1.0;
+            let mut refuted = false;
+            for offset in 1..number_of_real_players {
+                let refuting_player_index = (player_index + offset) % number_of_real_players;
+                let shown_cards = cards.iter().copied().filter(|card| deal.hands[refuting_player_index].contains(card)).collect::<Vec<Card>>();
+                if !shown_cards.is_empty() {
+                    for card in shown_cards.iter() {
+                        let weight = 1.0 / shown_cards.len() as f64;
+                        *joint_weights.entry((solution, Some((refuting_player_index, *card)))).or_insert(0.0) += weight;
+                        *response_weights.entry(Some((refuting_player_index, *card))).or_insert(0.0) += weight;
+                    }
+                    refuted = true;
+                    break;
+                }
+            }
+            if !refuted {
+                *joint_weights.entry((solution, None)).or_insert(0.0) += 1.0;
+                *response_weights.entry(None).or_insert(0.0) += 1.0;
+            }
+        }
+        let total = deals.len() as f64;
+        let mut information = 0.0;
+        for ((solution, response), weight) in joint_weights.iter() {
+            let p_joint = weight / total;
+            let p_solution = solution_weights[solution] / total;
+            let p_response = response_weights[response] / total;
+            information += p_joint * (p_joint / (p_solution * p_response)).log2();
+        }
+        return information;
+    }
+
+    pub fn board(self: &Self) -> &Board {
+        return &self.board;
+    }
+
+    pub fn best_suggestion_in_room(self: &Self, room: Card) -> RoomSuggestion {
+        return *self.room_suggestions.iter().find(|suggestion| suggestion.room == room).unwrap();
+    }
+
+    // is_none_or() would be simpler, but it needs Rust 1.82.
+    #[allow(clippy::unnecessary_map_or)]
+    fn room_reward(self: &Self, position: BoardPosition, target_room: Option<Card>) -> f64 {
+        return match position {
+            BoardPosition::Room(room) if target_room.map_or(true, |target_room| target_room == room) => self.best_suggestion_in_room(room).expected_information,
+            _ => 0.0
+        };
+    }
+
+    // What it's worth to end up at each position, if we stop there (for
+    // rooms, this means making a suggestion).  Corridor squares are worth
+    // what the next turn from there is worth, discounted.
+    fn landing_values(self: &Self, turns_ahead: usize, target_room: Option<Card>) -> Vec<f64> {
+        let positions = self.board.positions();
+        let mut values = vec![0.0; positions.len()];
+        for _ in 0..turns_ahead {
+            let landing_values = self.landing_values_from(&values, target_room);
+            values = (0..positions.len()).map(|index| self.turn_value(index, &landing_values, target_room).1).collect();
+        }
+        return self.landing_values_from(&values, target_room);
+    }
+
+    fn landing_values_from(self: &Self, next_turn_values: &[f64], target_room: Option<Card>) -> Vec<f64> {
+        return self.board.positions().iter().zip(next_turn_values.iter()).map(|(position, next_turn_value)| match position {
+            BoardPosition::Room(_) => self.room_reward(*position, target_room),
+            BoardPosition::Square { .. } => self.discount * next_turn_value
+        }).collect();
+    }
+
+    // The best thing to do at the start of a turn from this position, and
+    // what it's worth.
+    fn turn_value(self: &Self, from_index: usize, landing_values: &[f64], target_room: Option<Card>) -> (TurnChoice, f64) {
+        let roll_probabilities = self.board.roll_probabilities();
+        let max_roll = roll_probabilities.len() - 1;
+        let mut best_within = vec![0.0; max_roll + 1];
+        for (index, distance) in self.board.distances_from(from_index).iter().enumerate() {
+            if let Some(distance) = distance {
+                if *distance >= 1 && *distance <= max_roll {
+                    best_within[*distance] = f64::max(best_within[*distance], landing_values[index]);
+                }
+            }
+        }
+        for distance in 1..=max_roll {
+            best_within[distance] = f64::max(best_within[distance], best_within[distance - 1]);
+        }
+        let roll_value = roll_probabilities.iter().zip(best_within.iter()).map(|(probability, value)| probability * value).sum::<f64>();
+        if let BoardPosition::Room(room) = self.board.positions()[from_index] {
+            if let Some(other_room) = self.board.secret_passage(room) {
+                let passage_value = self.room_reward(BoardPosition::Room(other_room), target_room);
+                if passage_value > roll_value {
+                    return (TurnChoice::SecretPassage(other_room), passage_value);
+                }
+            }
+        }
+        return (TurnChoice::Roll, roll_value);
+    }
+
+    // Whether to take a secret passage or roll, and how much information
+    // we expect to get (discounted for waiting) by playing well from here.
+    pub fn choose_turn(self: &Self, from: BoardPosition, turns_ahead: usize) -> (TurnChoice, f64) {
+        let landing_values = self.landing_values(turns_ahead.saturating_sub(1), None);
+        return self.turn_value(self.board.index_of(from), &landing_values, None);
+    }
+
+    // Where to move after rolling, and what that's worth.  Returns None if
+    // there's nowhere to go.
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn best_move(self: &Self, from: BoardPosition, roll: usize, turns_ahead: usize) -> Option<(BoardPosition, f64)> {
+        let landing_values = self.landing_values(turns_ahead.saturating_sub(1), None);
+        let mut best: Option<(BoardPosition, f64)> = None;
+        for position in self.board.positions_reachable(from, roll) {
+            let value = landing_values[self.board.index_of(position)];
+            if best.map_or(true, |(_, best_value)| value > best_value) {
+                best = Some((position, value));
+            }
+        }
+        return best;
+    }
+
+    // Every room, with how much it's worth to head there next (counting only
+    // the suggestion in that room), best first.
+    pub fn rank_rooms(self: &Self, from: BoardPosition, turns_ahead: usize) -> Vec<(RoomSuggestion, f64)> {
+        let from_index = self.board.index_of(from);
+        let mut ranked = self.room_suggestions.iter().map(|suggestion| {
+            let landing_values = self.landing_values(turns_ahead.saturating_sub(1), Some(suggestion.room));
+            (*suggestion, self.turn_value(from_index, &landing_values, Some(suggestion.room)).1)
+        }).collect::<Vec<(RoomSuggestion, f64)>>();
+        ranked.sort_by(|(_, value1), (_, value2)| value2.partial_cmp(value1).unwrap());
+        return ranked;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::planner::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn make_planner() -> MovementPlanner {
+        // We know the suspect and weapon, and player 0 has some of the rooms,
+        // so suggesting those rooms tells us very little.
+        let mut engine = ClueEngine::new(3, None).unwrap();
+        for card in [Card::Kitchen, Card::Ballroom, Card::Hall, Card::Study, Card::MrGreen, Card::Revolver] {
+            engine.learn_info_on_card(0, card, true, true);
+        }
+        engine.learn_info_on_card(3, Card::ProfessorPlum, true, true);
+        engine.learn_info_on_card(3, Card::Knife, true, true);
+        return MovementPlanner::new(&engine, Board::standard(), 0, 500, StdRng::seed_from_u64(1)).unwrap();
+    }
+
+    #[test]
+    fn test_known_rooms_are_less_useful() {
+        let planner = make_planner();
+        // Not quite zero, since who has the other cards says a little about
+        // who has room for the rooms.
+        assert!(planner.best_suggestion_in_room(Card::Kitchen).expected_information < 0.1);
+        assert!(planner.best_suggestion_in_room(Card::Study).expected_information < 0.1);
+        let lounge = planner.best_suggestion_in_room(Card::Lounge);
+        assert!(lounge.expected_information > 0.5);
+        // There are only 5 possible solutions
+        assert!(lounge.expected_information <= 5.0f64.log2());
+    }
+
+    #[test]
+    fn test_secret_passage_to_useful_room() {
+        let planner = make_planner();
+        // The Lounge is useful and the passage gets us there right away.
+        assert_eq!(TurnChoice::SecretPassage(Card::Lounge), planner.choose_turn(BoardPosition::Room(Card::Conservatory), 3).0);
+        // There's nothing to learn in the Study, so rolling is better.
+        assert_eq!(TurnChoice::Roll, planner.choose_turn(BoardPosition::Room(Card::Kitchen), 3).0);
+    }
+
+    #[test]
+    fn test_best_move_enters_reachable_room() {
+        let planner = make_planner();
+        let start = BoardPosition::Square { row: 24, column: 7 };
+        assert_eq!(Some(BoardPosition::Room(Card::Lounge)), planner.best_move(start, 6, 3).map(|(position, _)| position));
+        let ranked = planner.rank_rooms(start, 3);
+        assert_eq!(9, ranked.len());
+        assert!(ranked.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        // Looking further ahead can only find more
+        assert!(planner.choose_turn(start, 4).1 >= planner.choose_turn(start, 1).1);
+    }
+
+    #[test]
+    fn test_needs_deals() {
+        let engine = ClueEngine::new(3, None).unwrap();
+        assert!(MovementPlanner::from_deals(&engine, Board::standard(), 0, &[]).is_err());
+    }
+}
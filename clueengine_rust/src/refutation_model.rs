@@ -0,0 +1,307 @@
+// Which card a player shows when they could refute with more than one.
+//
+// Players have habits: some would rather show a room than a suspect, say.
+// We model this by giving each player a weight for each type of card, and
+// assume they show each card they could show with probability proportional
+// to its type's weight.  The weights are learned from games where we know
+// which card was shown and which cards the player could have shown.
+//
+// Players sit in different seats from game to game, so habits are kept by
+// player name, and each RecordedGame says who sat where.
+
+use crate::{Card, CardSet, CardType, CardUtils, Deal};
+use std::collections::HashMap;
+
+// One refutation from a recorded game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefutationObservation {
+    pub player: String,
+    // The suggested cards the player had
+    pub held_cards: Vec<Card>,
+    pub card_shown: Card,
+}
+
+impl RefutationObservation {
+    // Returns None if the player in this seat didn't have card_shown.
+    pub fn from_deal(deal: &Deal, player: &str, player_index: usize, suggested_cards: [Card; 3], card_shown: Card) -> Option<RefutationObservation> {
+        let hand = deal.hands.get(player_index)?;
+        if !hand.contains(&card_shown) {
+            return None;
+        }
+        let held_cards = suggested_cards.iter().copied().filter(|card| hand.contains(card)).collect();
+        return Some(RefutationObservation { player: String::from(player), held_cards, card_shown });
+    }
+}
+
+// A finished game where we know everyone's hand and which card was shown
+// for each refutation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedGame {
+    // The players' names, in seat order
+    pub players: Vec<String>,
+    pub deal: Deal,
+    // (refuting player's seat, suggested cards, card shown)
+    pub refutations: Vec<(usize, [Card; 3], Card)>,
+}
+
+impl RecordedGame {
+    pub fn observations(self: &Self) -> Result<Vec<RefutationObservation>, String> {
+        return self.refutations.iter().map(|(player_index, suggested_cards, card_shown)| {
+            let player = self.players.get(*player_index).ok_or_else(|| format!("No player in seat {}", player_index))?;
+            return RefutationObservation::from_deal(&self.deal, player, *player_index, *suggested_cards, *card_shown)
+                .ok_or_else(|| format!("{} showed {:?} but didn't have it", player, card_shown));
+        }).collect();
+    }
+
+    // format is
+    // the players' names in seat order, separated by ','
+    // each player's hand and then the solution as card letters (see
+    // CardUtils::card_from_char()), separated by '.'
+    // then one line per refutation: the refuting player's seat, the three
+    // suggested cards and the card shown, separated by spaces
+    // for example:
+    // Alice,Bob
+    // ABGHMN.CDIJOP.EKQ
+    // 1 CHM C
+    pub fn load_from_string(s: &str) -> Result<RecordedGame, String> {
+        let mut lines = s.lines().map(|line| line.trim()).filter(|line| !line.is_empty());
+        let players = lines.next().ok_or_else(|| String::from("Missing players in recorded game"))?
+            .split(',').map(|player| String::from(player.trim())).collect::<Vec<String>>();
+        let hands = lines.next().ok_or_else(|| String::from("Missing hands in recorded game"))?
+            .split('.').map(|hand| hand.chars().map(CardUtils::card_from_char).collect::<Result<CardSet, String>>()).collect::<Result<Vec<CardSet>, String>>()?;
+        if hands.len() != players.len() + 1 {
+            return Err(format!("Expected {} hands (including the solution) but got {}", players.len() + 1, hands.len()));
+        }
+        let mut refutations = vec![];
+        for line in lines {
+            let parts = line.split_whitespace().collect::<Vec<&str>>();
+            if parts.len() != 3 || parts[1].chars().count() != 3 || parts[2].chars().count() != 1 {
+                return Err(format!("Expected a seat, three cards and a card in refutation \"{}\"", line));
+            }
+            let player_index = parts[0].parse::<usize>().map_err(|_| format!("Couldn't parse seat \"{}\"", parts[0]))?;
+            let mut suggested_cards = [Card::ProfessorPlum; 3];
+            for (suggested_card, ch) in suggested_cards.iter_mut().zip(parts[1].chars()) {
+                *suggested_card = CardUtils::card_from_char(ch)?;
+            }
+            let card_shown = CardUtils::card_from_char(parts[2].chars().next().unwrap())?;
+            refutations.push((player_index, suggested_cards, card_shown));
+        }
+        let game = RecordedGame { players, deal: Deal { hands }, refutations };
+        // Make sure every refutation makes sense.
+        game.observations()?;
+        return Ok(game);
+    }
+
+    // Any number of games in the format load_from_string() uses, separated
+    // by blank lines.
+    pub fn load_games_from_string(s: &str) -> Result<Vec<RecordedGame>, String> {
+        let mut games = vec![];
+        let mut current_game = String::new();
+        for line in s.lines().chain(std::iter::once("")) {
+            if line.trim().is_empty() {
+                if !current_game.is_empty() {
+                    games.push(RecordedGame::load_from_string(&current_game)?);
+                    current_game.clear();
+                }
+            }
+            else {
+                current_game += line;
+                current_game += "\n";
+            }
+        }
+        return Ok(games);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefutationModel {
+    // Indexed in the same order as CardUtils::all_card_types()
+    default_type_weights: [f64; 3],
+    player_type_weights: HashMap<String, [f64; 3]>,
+}
+
+fn type_index(card: Card) -> usize {
+    return match CardUtils::card_type(card) {
+        CardType::Suspect => 0,
+        CardType::Weapon => 1,
+        CardType::Room => 2,
+    };
+}
+
+impl RefutationModel {
+    // How many times to iterate when learning the weights.
+    const LEARNING_ITERATIONS: usize = 200;
+
+    // Every card is equally likely to be shown.
+    pub fn uniform() -> RefutationModel {
+        return RefutationModel { default_type_weights: [1.0; 3], player_type_weights: HashMap::new() };
+    }
+
+    // Learns weights for every player in observations, along with weights
+    // from everyone's observations for players we haven't seen.  Only
+    // refutations where the player had a choice tell us anything.
+    pub fn learn(observations: &[RefutationObservation]) -> RefutationModel {
+        let default_type_weights = RefutationModel::learn_type_weights(observations.iter());
+        let mut players = observations.iter().map(|observation| observation.player.as_str()).collect::<Vec<&str>>();
+        players.sort();
+        players.dedup();
+        let player_type_weights = players.into_iter().map(|player| {
+            (String::from(player), RefutationModel::learn_type_weights(observations.iter().filter(|observation| observation.player == player)))
+        }).collect();
+        return RefutationModel { default_type_weights, player_type_weights };
+    }
+
+    pub fn learn_from_games(games: &[RecordedGame]) -> Result<RefutationModel, String> {
+        let mut observations = vec![];
+        for game in games {
+            observations.extend(game.observations()?);
+        }
+        return Ok(RefutationModel::learn(&observations));
+    }
+
+    // The maximum likelihood weights for a Luce choice model, found with
+    // the usual minorization-maximization updates.  Every type starts out
+    // with one made-up observation where it was picked over the other two,
+    // so a few observations don't lead to extreme weights.
+    fn learn_type_weights<'a, I: Iterator<Item=&'a RefutationObservation>>(observations: I) -> [f64; 3] {
+        // (types that could have been shown, type that was shown)
+        let mut choices = (0..3).map(|shown_type| (vec![0, 1, 2], shown_type)).collect::<Vec<(Vec<usize>, usize)>>();
+        for observation in observations {
+            let mut types = observation.held_cards.iter().map(|card| type_index(*card)).collect::<Vec<usize>>();
+            types.sort();
+            types.dedup();
+            if types.len() > 1 {
+                choices.push((types, type_index(observation.card_shown)));
+            }
+        }
+        let mut wins = [0.0; 3];
+        for (_, shown_type) in choices.iter() {
+            wins[*shown_type] += 1.0;
+        }
+        let mut weights = [1.0; 3];
+        for _ in 0..RefutationModel::LEARNING_ITERATIONS {
+            let mut denominators = [0.0; 3];
+            for (types, _) in choices.iter() {
+                let total_weight = types.iter().map(|type_index| weights[*type_index]).sum::<f64>();
+                for type_index in types.iter() {
+                    denominators[*type_index] += 1.0 / total_weight;
+                }
+            }
+            for type_index in 0..3 {
+                weights[type_index] = wins[type_index] / denominators[type_index];
+            }
+            // Only the ratios matter, so keep them from drifting.
+            let total = weights.iter().sum::<f64>();
+            for weight in weights.iter_mut() {
+                *weight *= 3.0 / total;
+            }
+        }
+        return weights;
+    }
+
+    // Suspect, weapon and room weights for this player.  Players we don't
+    // have observations for (or don't know the name of) get the weights
+    // learned from everyone.
+    pub fn type_weights(self: &Self, player: Option<&str>) -> [f64; 3] {
+        return *player.and_then(|player| self.player_type_weights.get(player)).unwrap_or(&self.default_type_weights);
+    }
+
+    // The chance that this player shows card when they have held_cards.
+    pub fn show_probability(self: &Self, player: Option<&str>, held_cards: &[Card], card: Card) -> f64 {
+        if !held_cards.contains(&card) {
+            return 0.0;
+        }
+        let weights = self.type_weights(player);
+        let total_weight = held_cards.iter().map(|held_card| weights[type_index(*held_card)]).sum::<f64>();
+        return weights[type_index(card)] / total_weight;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::refutation_model::*;
+
+    fn observation(player: &str, held_cards: Vec<Card>, card_shown: Card) -> RefutationObservation {
+        return RefutationObservation { player: String::from(player), held_cards, card_shown };
+    }
+
+    #[test]
+    fn test_uniform() {
+        let model = RefutationModel::uniform();
+        assert_eq!(0.5, model.show_probability(Some("Alice"), &[Card::Knife, Card::Hall], Card::Hall));
+        assert_eq!(0.0, model.show_probability(Some("Alice"), &[Card::Knife, Card::Hall], Card::ProfessorPlum));
+        assert_eq!(1.0, model.show_probability(None, &[Card::Knife], Card::Knife));
+    }
+
+    #[test]
+    fn test_learns_preference() {
+        let mut observations = vec![];
+        for _ in 0..20 {
+            // Bob always shows rooms
+            observations.push(observation("Bob", vec![Card::ProfessorPlum, Card::Hall], Card::Hall));
+            observations.push(observation("Bob", vec![Card::Knife, Card::Study], Card::Study));
+            // Carol doesn't care
+            observations.push(observation("Carol", vec![Card::ProfessorPlum, Card::Hall], Card::ProfessorPlum));
+            observations.push(observation("Carol", vec![Card::ProfessorPlum, Card::Hall], Card::Hall));
+        }
+        // No choice, so this doesn't count
+        observations.push(observation("Carol", vec![Card::Knife], Card::Knife));
+        let model = RefutationModel::learn(&observations);
+        assert!(model.show_probability(Some("Bob"), &[Card::MrGreen, Card::Lounge], Card::Lounge) > 0.9);
+        assert!((model.show_probability(Some("Carol"), &[Card::MrGreen, Card::Lounge], Card::Lounge) - 0.5).abs() < 0.05);
+        // Alice gets everyone's habits, which lean towards rooms
+        assert!(model.show_probability(Some("Alice"), &[Card::MrGreen, Card::Lounge], Card::Lounge) > 0.5);
+        assert_eq!(model.type_weights(Some("Alice")), model.type_weights(None));
+        let total = [Card::MrGreen, Card::Rope, Card::Lounge].iter().map(|card| model.show_probability(Some("Bob"), &[Card::MrGreen, Card::Rope, Card::Lounge], *card)).sum::<f64>();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_from_deal() {
+        let deal = Deal { hands: vec![
+            [Card::ProfessorPlum, Card::Hall].iter().copied().collect(),
+            [Card::Knife].iter().copied().collect(),
+        ] };
+        let observation = RefutationObservation::from_deal(&deal, "Alice", 0, [Card::ProfessorPlum, Card::Knife, Card::Hall], Card::Hall).unwrap();
+        assert_eq!("Alice", observation.player);
+        assert_eq!(vec![Card::ProfessorPlum, Card::Hall], observation.held_cards);
+        assert_eq!(None, RefutationObservation::from_deal(&deal, "Alice", 0, [Card::ProfessorPlum, Card::Knife, Card::Hall], Card::Knife));
+    }
+
+    #[test]
+    fn test_habits_follow_player_between_seats() {
+        // Bob always shows the room, from whichever seat he's in.
+        let games = RecordedGame::load_games_from_string("
+            Alice,Bob,Carol
+            ABCGHM.DIJNOP.EKQRST.FLU
+            1 AIN N
+            1 BJO O
+
+            Bob,Carol,Alice
+            DIJNOP.EKQRST.ABCGHM.FLU
+            0 CIP P
+            2 AGR A
+        ").unwrap();
+        assert_eq!(2, games.len());
+        assert_eq!(vec!["Bob", "Carol", "Alice"], games[1].players);
+        let observations = games[1].observations().unwrap();
+        assert_eq!("Bob", observations[0].player);
+        assert_eq!(vec![CardUtils::card_from_char('I').unwrap(), CardUtils::card_from_char('P').unwrap()], observations[0].held_cards);
+        assert_eq!("Alice", observations[1].player);
+
+        let model = RefutationModel::learn_from_games(&games).unwrap();
+        assert!(model.show_probability(Some("Bob"), &[Card::MrGreen, Card::Lounge], Card::Lounge) > 0.5);
+        assert!(model.show_probability(Some("Alice"), &[Card::ProfessorPlum, Card::Lounge], Card::ProfessorPlum) > 0.5);
+    }
+
+    #[test]
+    fn test_load_recorded_game_errors() {
+        // Bob doesn't have the Knife
+        assert!(RecordedGame::load_from_string("Alice,Bob\nABCDGHMN.IJKOPQ.ELR\n1 AGM G").is_err());
+        // No solution
+        assert!(RecordedGame::load_from_string("Alice,Bob\nABCDGHMN.IJKOPQ\n").is_err());
+        assert!(RecordedGame::load_from_string("Alice,Bob\nABCDGHMN.IJKOPQ.ELR\n2 AGM G").is_err());
+        assert!(RecordedGame::load_from_string("Alice,Bob\nABCDGHMN.IJKOPQ.ELR\n0 AG G").is_err());
+        assert!(RecordedGame::load_from_string("").is_err());
+    }
+}
@@ -0,0 +1,287 @@
+// Inference that tolerates a few mistakes in what was recorded.
+//
+// It's easy to tap the wrong refuting player, and a single wrong entry can
+// make everything we know inconsistent, at which point the engine can't tell
+// us anything.  An ObservationLog keeps everything that was recorded, in
+// order, so we can look for the fewest observations that have to be wrong
+// for the rest to be consistent.  Every way of ignoring that many
+// observations is weighted by how many deals are consistent with the rest,
+// which is how likely it is if each deal is equally likely and a mistaken
+// entry could have been anything.  Those weights tell us which observations
+// are most likely wrong, and give probabilities to show until the mistake is
+// fixed.
+
+use crate::{Card, ClueCnf, ClueEngine, DealCounter, SimulationConfig, SuggestionResponse, WeightedSimulationData};
+use std::collections::HashMap;
+
+// One thing that was recorded during the game.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum Observation {
+    CardInfo { player_index: usize, card: Card, has_card: bool },
+    Suggestion { suggesting_player_index: usize, cards: [Card; 3], response: SuggestionResponse },
+}
+
+impl Observation {
+    fn apply(self: &Self, engine: &mut ClueEngine) {
+        match self {
+            Observation::CardInfo { player_index, card, has_card } => {
+                engine.learn_info_on_card(*player_index, *card, *has_card, true);
+            },
+            Observation::Suggestion { suggesting_player_index, cards, response } => {
+                engine.learn_suggest(*suggesting_player_index, cards[0], cards[1], cards[2], response.refuting_player_index(), response.card_shown());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ObservationLog {
+    // An engine that hasn't learned anything yet.
+    initial_engine: ClueEngine,
+    observations: Vec<Observation>,
+}
+
+// One way of explaining the log with as few mistakes as possible.
+#[derive(Debug, Clone)]
+pub struct RobustCandidate {
+    // Indices into the log of the observations assumed to be wrong.
+    pub ignored_observations: Vec<usize>,
+    // How likely this explanation is, compared to the others.  The weights of
+    // all the candidates add up to 1.
+    pub weight: f64,
+    // What we know if the ignored observations are left out.
+    pub engine: ClueEngine,
+}
+
+#[derive(Debug, Clone)]
+pub struct RobustInference {
+    // How many observations have to be wrong.  Zero means the log is
+    // consistent.
+    pub num_mistakes: usize,
+    pub candidates: Vec<RobustCandidate>,
+}
+
+impl ObservationLog {
+    pub fn new(number_of_players: u8, number_of_cards_per_player: Option<&Vec<u8>>) -> Result<ObservationLog, String> {
+        return Ok(ObservationLog {
+            initial_engine: ClueEngine::new(number_of_players, number_of_cards_per_player)?,
+            observations: vec![],
+        });
+    }
+
+    // Returns the index of the observation in the log.
+    pub fn record(self: &mut Self, observation: Observation) -> usize {
+        self.observations.push(observation);
+        return self.observations.len() - 1;
+    }
+
+    pub fn observations(self: &Self) -> &Vec<Observation> {
+        return &self.observations;
+    }
+
+    // Fix an observation that turned out to be wrong.
+    pub fn replace(self: &mut Self, index: usize, observation: Observation) -> Result<(), String> {
+        let entry = self.observations.get_mut(index).ok_or(format!("No observation {} in the log", index))?;
+        *entry = observation;
+        return Ok(());
+    }
+
+    pub fn remove(self: &mut Self, index: usize) -> Result<Observation, String> {
+        if index >= self.observations.len() {
+            return Err(format!("No observation {} in the log", index));
+        }
+        return Ok(self.observations.remove(index));
+    }
+
+    // Learns every observation except the ones in skip, in order.
+    pub fn build_engine(self: &Self, skip: &[usize]) -> ClueEngine {
+        let mut engine = self.initial_engine.clone();
+        for (index, observation) in self.observations.iter().enumerate() {
+            if !skip.contains(&index) {
+                observation.apply(&mut engine);
+            }
+        }
+        return engine;
+    }
+
+    // Whether the observations we didn't skip can all be true.  The engine
+    // quietly drops a refutation when it already knows the refuting player
+    // has none of the cards, so those are checked here too.
+    fn is_consistent(self: &Self, engine: &ClueEngine, skip: &[usize]) -> bool {
+        if !engine.is_consistent() {
+            return false;
+        }
+        let cnf = ClueCnf::from_engine(engine);
+        let mut solver = cnf.to_solver();
+        for (index, observation) in self.observations.iter().enumerate() {
+            if skip.contains(&index) {
+                continue;
+            }
+            if let Observation::Suggestion { cards, response: SuggestionResponse::Refuted { player_index, card_shown: None }, .. } = observation {
+                let clause = cards.iter().map(|card| cnf.ownership_variable(*player_index, *card)).collect::<Vec<i32>>();
+                solver.add_clause(&clause);
+            }
+        }
+        return solver.solve(&[]).is_some();
+    }
+
+    // Finds the fewest observations (at most max_mistakes) that have to be
+    // ignored for the rest of the log to be consistent, and every way of
+    // picking them.  This tries every combination, so keep max_mistakes
+    // small.
+    pub fn robust_inference(self: &Self, max_mistakes: usize) -> Result<RobustInference, String> {
+        // engine_prefixes[i] has learned the first i observations, so we
+        // only have to replay the ones after the first ignored one.
+        let mut engine_prefixes = vec![self.initial_engine.clone()];
+        for observation in self.observations.iter() {
+            let mut engine = engine_prefixes.last().unwrap().clone();
+            observation.apply(&mut engine);
+            engine_prefixes.push(engine);
+        }
+        for num_mistakes in 0..=max_mistakes.min(self.observations.len()) {
+            let mut candidates = vec![];
+            for skip in combinations(self.observations.len(), num_mistakes) {
+                let first_skipped = skip.first().copied().unwrap_or(self.observations.len());
+                let mut engine = engine_prefixes[first_skipped].clone();
+                for index in first_skipped..self.observations.len() {
+                    if !skip.contains(&index) {
+                        self.observations[index].apply(&mut engine);
+                    }
+                }
+                if self.is_consistent(&engine, &skip) {
+                    candidates.push(RobustCandidate { ignored_observations: skip, weight: 0.0, engine });
+                }
+            }
+            if !candidates.is_empty() {
+                set_candidate_weights(&mut candidates);
+                return Ok(RobustInference { num_mistakes, candidates });
+            }
+        }
+        return Err(format!("The observations are inconsistent even when ignoring up to {} of them", max_mistakes));
+    }
+}
+
+// Weights candidates by their number of consistent deals.  If we can't count
+// deals for all of them, every candidate is treated as equally likely.
+fn set_candidate_weights(candidates: &mut [RobustCandidate]) {
+    let counts = candidates.iter()
+        .map(|candidate| DealCounter::new(&candidate.engine).ok().map(|counter| counter.total_count() as f64))
+        .collect::<Option<Vec<f64>>>()
+        .filter(|counts| counts.iter().sum::<f64>() > 0.0)
+        .unwrap_or_else(|| vec![1.0; candidates.len()]);
+    let total = counts.iter().sum::<f64>();
+    for (candidate, count) in candidates.iter_mut().zip(counts) {
+        candidate.weight = count / total;
+    }
+}
+
+// Every sorted way of picking k of the numbers 0..n.
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    let mut result = vec![];
+    let mut current = vec![];
+    add_combinations(0, n, k, &mut current, &mut result);
+    return result;
+}
+
+fn add_combinations(start: usize, n: usize, k: usize, current: &mut Vec<usize>, result: &mut Vec<Vec<usize>>) {
+    if current.len() == k {
+        result.push(current.clone());
+        return;
+    }
+    for i in start..n {
+        current.push(i);
+        add_combinations(i + 1, n, k, current, result);
+        current.pop();
+    }
+}
+
+impl RobustInference {
+    // How likely each observation is to be wrong, most likely first.
+    // Observations that aren't ignored by any candidate are left out.
+    pub fn suspicious_observations(self: &Self) -> Vec<(usize, f64)> {
+        let mut probabilities: HashMap<usize, f64> = HashMap::new();
+        for candidate in self.candidates.iter() {
+            for index in candidate.ignored_observations.iter() {
+                *probabilities.entry(*index).or_insert(0.0) += candidate.weight;
+            }
+        }
+        let mut result = probabilities.into_iter().collect::<Vec<(usize, f64)>>();
+        result.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+        return result;
+    }
+
+    // The probability each player has each card, mixing the simulations for
+    // each candidate by its weight.
+    pub fn probabilities(self: &Self, config: &SimulationConfig) -> WeightedSimulationData {
+        let mut result = WeightedSimulationData::new();
+        for candidate in self.candidates.iter() {
+            let (simulation_data, num_simulations) = candidate.engine.do_simulation_with_config(config);
+            if num_simulations <= 0 {
+                continue;
+            }
+            for (card, counts) in simulation_data {
+                let probabilities = result.entry(card).or_insert_with(|| vec![0.0; counts.len()]);
+                for (probability, count) in probabilities.iter_mut().zip(counts) {
+                    *probability += candidate.weight * count as f64 / num_simulations as f64;
+                }
+            }
+        }
+        // Normalize in case some candidates didn't get any simulations.
+        for probabilities in result.values_mut() {
+            let total = probabilities.iter().sum::<f64>();
+            if total > 0.0 {
+                probabilities.iter_mut().for_each(|probability| *probability /= total);
+            }
+        }
+        return result;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::robust::*;
+
+    // Player 1 is recorded as refuting a suggestion of cards we've been told
+    // (several ways) that they don't have.
+    fn log_with_mistyped_refuter() -> ObservationLog {
+        let mut log = ObservationLog::new(3, None).unwrap();
+        let cards = [Card::MrsPeacock, Card::Knife, Card::Hall];
+        log.record(Observation::Suggestion { suggesting_player_index: 0, cards, response: SuggestionResponse::NotRefuted });
+        for card in cards {
+            log.record(Observation::CardInfo { player_index: 1, card, has_card: false });
+        }
+        log.record(Observation::Suggestion { suggesting_player_index: 2, cards, response: SuggestionResponse::Refuted { player_index: 1, card_shown: None } });
+        return log;
+    }
+
+    #[test]
+    fn test_combinations() {
+        assert_eq!(vec![vec![]] as Vec<Vec<usize>>, combinations(3, 0));
+        assert_eq!(vec![vec![0, 1], vec![0, 2], vec![1, 2]], combinations(3, 2));
+    }
+
+    #[test]
+    fn test_consistent_log_needs_no_mistakes() {
+        let mut log = log_with_mistyped_refuter();
+        log.remove(4).unwrap();
+        let inference = log.robust_inference(1).unwrap();
+        assert_eq!(0, inference.num_mistakes);
+        assert_eq!(1, inference.candidates.len());
+        assert!(inference.suspicious_observations().is_empty());
+    }
+
+    #[test]
+    fn test_finds_mistyped_refuter() {
+        let log = log_with_mistyped_refuter();
+        assert!(log.robust_inference(0).is_err());
+        let inference = log.robust_inference(2).unwrap();
+        assert_eq!(1, inference.num_mistakes);
+        assert_eq!(vec![(4, 1.0)], inference.suspicious_observations());
+
+        let config = SimulationConfig::builder().num_simulations(500).build().unwrap();
+        let probabilities = inference.probabilities(&config);
+        let hall = probabilities.get(&Card::Hall).unwrap();
+        assert_eq!(0.0, hall[1]);
+        assert!((hall.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+}
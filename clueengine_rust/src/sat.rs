@@ -0,0 +1,258 @@
+// A small DPLL satisfiability solver.  The problems we give it (see cnf.rs)
+// only have a few hundred variables, so there's no clause learning, just unit
+// propagation with two watched literals per clause.
+//
+// Literals use the DIMACS convention: variable v (starting at 1) is v if
+// true and -v if false.
+
+use std::cmp::min;
+
+#[derive(Debug, Clone)]
+pub struct SatSolver {
+    num_variables: usize,
+    // Clauses with at least two literals.  The first two literals of each
+    // clause are the ones being watched.
+    clauses: Vec<Vec<i32>>,
+    // Literals that must be true.
+    units: Vec<i32>,
+    // Set if we were given an empty clause.
+    trivially_unsatisfiable: bool,
+    // For each literal, the clauses that are watching it.
+    watches: Vec<Vec<usize>>,
+}
+
+// Where we are in the search.
+struct Search {
+    values: Vec<Option<bool>>,
+    trail: Vec<i32>,
+    // The next index in trail to propagate
+    propagated: usize,
+    // For each decision, where it is in the trail and whether we've already
+    // tried the other value.
+    decisions: Vec<(usize, bool)>,
+}
+
+impl Search {
+    fn value(self: &Self, literal: i32) -> Option<bool> {
+        return self.values[literal.unsigned_abs() as usize].map(|value| value == (literal > 0));
+    }
+
+    // Returns false if the literal is already false.
+    fn assign(self: &mut Self, literal: i32) -> bool {
+        match self.value(literal) {
+            Some(value) => return value,
+            None => {
+                self.values[literal.unsigned_abs() as usize] = Some(literal > 0);
+                self.trail.push(literal);
+                return true;
+            }
+        }
+    }
+
+    fn undo_to(self: &mut Self, trail_len: usize) {
+        for literal in self.trail.drain(trail_len..) {
+            self.values[literal.unsigned_abs() as usize] = None;
+        }
+        self.propagated = min(self.propagated, trail_len);
+    }
+}
+
+fn watch_index(literal: i32) -> usize {
+    return 2 * literal.unsigned_abs() as usize + if literal > 0 { 0 } else { 1 };
+}
+
+impl SatSolver {
+    pub fn new(num_variables: usize) -> SatSolver {
+        return SatSolver {
+            num_variables,
+            clauses: vec![],
+            units: vec![],
+            trivially_unsatisfiable: false,
+            watches: vec![vec![]; 2 * (num_variables + 1)],
+        };
+    }
+
+    pub fn num_variables(self: &Self) -> usize {
+        return self.num_variables;
+    }
+
+    pub fn add_clause(self: &mut Self, clause: &[i32]) {
+        let mut clause = clause.to_vec();
+        clause.sort_unstable();
+        clause.dedup();
+        assert!(clause.iter().all(|literal| *literal != 0 && literal.unsigned_abs() as usize <= self.num_variables), "Literal out of range");
+        if clause.iter().any(|literal| clause.contains(&-literal)) {
+            // Always true, so we can ignore it.
+            return;
+        }
+        match clause.len() {
+            0 => self.trivially_unsatisfiable = true,
+            1 => self.units.push(clause[0]),
+            _ => {
+                let clause_index = self.clauses.len();
+                self.watches[watch_index(clause[0])].push(clause_index);
+                self.watches[watch_index(clause[1])].push(clause_index);
+                self.clauses.push(clause);
+            }
+        }
+    }
+
+    // Returns a satisfying assignment (indexed by variable, so index 0 is
+    // unused) where all the assumptions are true, or None if there isn't one.
+    pub fn solve(self: &mut Self, assumptions: &[i32]) -> Option<Vec<bool>> {
+        if self.trivially_unsatisfiable {
+            return None;
+        }
+        let mut search = Search { values: vec![None; self.num_variables + 1], trail: vec![], propagated: 0, decisions: vec![] };
+        for literal in self.units.iter().chain(assumptions.iter()) {
+            if !search.assign(*literal) {
+                return None;
+            }
+        }
+        let mut next_variable = 1;
+        loop {
+            if self.propagate(&mut search) {
+                // Pick the next variable to decide.  Variables are decided in
+                // order, so anything before next_variable is already assigned.
+                while next_variable <= self.num_variables && search.values[next_variable].is_some() {
+                    next_variable += 1;
+                }
+                if next_variable > self.num_variables {
+                    return Some(search.values.iter().map(|value| value.unwrap_or(false)).collect());
+                }
+                // Most facts in a Clue game are "doesn't have", so try false first.
+                search.decisions.push((search.trail.len(), false));
+                search.assign(-(next_variable as i32));
+            }
+            else {
+                // Conflict, so go back to the last decision where we haven't
+                // tried the other value.
+                loop {
+                    match search.decisions.pop() {
+                        None => return None,
+                        Some((_, true)) => continue,
+                        Some((trail_len, false)) => {
+                            let literal = search.trail[trail_len];
+                            search.undo_to(trail_len);
+                            search.decisions.push((trail_len, true));
+                            search.assign(-literal);
+                            next_variable = min(next_variable, literal.unsigned_abs() as usize);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Returns false if there's a conflict.
+    fn propagate(self: &mut Self, search: &mut Search) -> bool {
+        while search.propagated < search.trail.len() {
+            let false_literal = -search.trail[search.propagated];
+            search.propagated += 1;
+            let mut watching = std::mem::take(&mut self.watches[watch_index(false_literal)]);
+            let mut i = 0;
+            let mut conflict = false;
+            while i < watching.len() {
+                let clause_index = watching[i];
+                let clause = &mut self.clauses[clause_index];
+                // Make sure the false literal is the second one.
+                if clause[0] == false_literal {
+                    clause.swap(0, 1);
+                }
+                if search.value(clause[0]) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+                // Look for another literal to watch.
+                if let Some(new_position) = (2..clause.len()).find(|position| search.value(clause[*position]) != Some(false)) {
+                    clause.swap(1, new_position);
+                    self.watches[watch_index(clause[1])].push(clause_index);
+                    watching.swap_remove(i);
+                    continue;
+                }
+                // Every other literal is false, so the first one has to be true.
+                if !search.assign(clause[0]) {
+                    conflict = true;
+                    break;
+                }
+                i += 1;
+            }
+            self.watches[watch_index(false_literal)] = watching;
+            if conflict {
+                return false;
+            }
+        }
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sat::*;
+
+    #[test]
+    fn test_solve_simple() {
+        let mut solver = SatSolver::new(3);
+        solver.add_clause(&[1, 2]);
+        solver.add_clause(&[-1, 3]);
+        solver.add_clause(&[-3]);
+        let model = solver.solve(&[]).unwrap();
+        assert_eq!(vec![false, false, true, false], model);
+        assert_eq!(None, solver.solve(&[1]));
+    }
+
+    #[test]
+    fn test_solve_empty_clause() {
+        let mut solver = SatSolver::new(1);
+        solver.add_clause(&[]);
+        assert_eq!(None, solver.solve(&[]));
+    }
+
+    #[test]
+    fn test_solve_pigeonhole() {
+        // Four pigeons in three holes; variable 3 * pigeon + hole + 1.
+        let mut solver = SatSolver::new(12);
+        for pigeon in 0..4 {
+            solver.add_clause(&[3 * pigeon + 1, 3 * pigeon + 2, 3 * pigeon + 3]);
+        }
+        for hole in 0..3 {
+            for pigeon1 in 0..4 {
+                for pigeon2 in (pigeon1 + 1)..4 {
+                    solver.add_clause(&[-(3 * pigeon1 + hole + 1), -(3 * pigeon2 + hole + 1)]);
+                }
+            }
+        }
+        assert_eq!(None, solver.solve(&[]));
+    }
+
+    #[test]
+    fn test_solve_brute_force() {
+        // Compare against trying every assignment on some small random problems.
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..200 {
+            let num_variables = 6;
+            let clauses = (0..rng.gen_range(1, 20)).map(|_| {
+                (0..rng.gen_range(1, 4)).map(|_| {
+                    let variable = rng.gen_range(1, num_variables + 1) as i32;
+                    if rng.gen::<bool>() { variable } else { -variable }
+                }).collect::<Vec<i32>>()
+            }).collect::<Vec<Vec<i32>>>();
+            let mut solver = SatSolver::new(num_variables);
+            for clause in clauses.iter() {
+                solver.add_clause(clause);
+            }
+            let satisfies = |model: &Vec<bool>| clauses.iter().all(|clause| clause.iter().any(|literal| model[literal.unsigned_abs() as usize] == (*literal > 0)));
+            let brute_force = (0..(1 << num_variables)).any(|bits: u32| {
+                let model = (0..=num_variables).map(|v| v > 0 && bits & (1 << (v - 1)) != 0).collect::<Vec<bool>>();
+                satisfies(&model)
+            });
+            match solver.solve(&[]) {
+                Some(model) => assert!(satisfies(&model)),
+                None => assert!(!brute_force)
+            }
+            assert_eq!(brute_force, solver.solve(&[]).is_some());
+        }
+    }
+}
@@ -0,0 +1,246 @@
+// Remembers simulation results, so simulating the same position again (or a
+// position where we know the same things, see
+// ClueEngine::write_to_canonical_string()) doesn't start over.  Results for
+// different sampling strategies are kept separately, since they don't count
+// deals the same way.
+//
+// The cache can be saved with write_to_string() and read back with
+// load_from_string(), so it can last longer than one process.  To keep each
+// position in its own file, use file_name() and load_entry_from_string().
+
+use crate::{Card, CardUtils, ClueEngine, SamplingStrategy, SimulationConfig, SimulationData};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq)]
+struct CachedSimulation {
+    simulation_data: SimulationData,
+    total_number_of_simulations: i32,
+}
+
+impl CachedSimulation {
+    // How many consistent deals were counted.
+    fn num_accepted_simulations(self: &Self) -> usize {
+        return self.simulation_data.get(&Card::ProfessorPlum).map_or(0, |counts| counts.iter().sum());
+    }
+
+    fn accumulate_from(self: &mut Self, simulation_data: &SimulationData, total_number_of_simulations: i32) {
+        for (card, counts) in simulation_data.iter() {
+            let cached_counts = self.simulation_data.entry(*card).or_insert_with(|| vec![0; counts.len()]);
+            for (cached_count, count) in cached_counts.iter_mut().zip(counts.iter()) {
+                *cached_count += count;
+            }
+        }
+        self.total_number_of_simulations += total_number_of_simulations;
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimulationCache {
+    // Keyed by the canonical string of the engine
+    entries: HashMap<(String, SamplingStrategy), CachedSimulation>,
+}
+
+impl SimulationCache {
+    pub fn new() -> SimulationCache {
+        return SimulationCache { entries: HashMap::new() };
+    }
+
+    pub fn len(self: &Self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(self: &Self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    // The results we already have for this position, if any.
+    pub fn get(self: &Self, engine: &ClueEngine, strategy: SamplingStrategy) -> Option<(SimulationData, i32)> {
+        return self.entries.get(&(engine.write_to_canonical_string(), strategy))
+            .map(|cached| (cached.simulation_data.clone(), cached.total_number_of_simulations));
+    }
+
+    // Like ClueEngine::do_simulation_with_config(), but if we already have
+    // at least config.min_accepted_simulations() consistent deals for this
+    // position, returns those right away.  Otherwise does more simulations
+    // and adds them to what we had.
+    pub fn simulate(self: &mut Self, engine: &ClueEngine, config: &SimulationConfig) -> (SimulationData, i32) {
+        if self.has_enough_simulations(engine, config) {
+            return self.get(engine, config.strategy()).unwrap();
+        }
+        return self.refine(engine, config);
+    }
+
+    // Whether simulate() would return what we already have.
+    pub fn has_enough_simulations(self: &Self, engine: &ClueEngine, config: &SimulationConfig) -> bool {
+        return self.entries.get(&(engine.write_to_canonical_string(), config.strategy()))
+            .is_some_and(|cached| cached.num_accepted_simulations() >= config.min_accepted_simulations());
+    }
+
+    // Does more simulations for this position and adds them to what we
+    // already had, so the results keep getting more accurate.
+    pub fn refine(self: &mut Self, engine: &ClueEngine, config: &SimulationConfig) -> (SimulationData, i32) {
+        let (simulation_data, total_number_of_simulations) = engine.do_simulation_with_config(config);
+        let cached = self.entries.entry((engine.write_to_canonical_string(), config.strategy())).or_insert_with(|| CachedSimulation {
+            simulation_data: CardUtils::all_cards().map(|card| (card, vec![0; engine.player_data.len()])).collect(),
+            total_number_of_simulations: 0,
+        });
+        cached.accumulate_from(&simulation_data, total_number_of_simulations);
+        return (cached.simulation_data.clone(), cached.total_number_of_simulations);
+    }
+
+    // One line per entry:
+    // <strategy> <canonical session string> <total number of simulations> <counts>
+    // where the counts are the counts for each player separated by commas,
+    // for each card in order separated by semicolons.
+    pub fn write_to_string(self: &Self) -> String {
+        let mut lines = self.entries.iter().map(|((session, strategy), cached)| {
+            let counts = CardUtils::all_cards().map(|card| {
+                cached.simulation_data.get(&card).unwrap().iter().map(|count| count.to_string()).collect::<Vec<String>>().join(",")
+            }).collect::<Vec<String>>().join(";");
+            format!("{:?} {} {} {}", strategy, session, cached.total_number_of_simulations, counts)
+        }).collect::<Vec<String>>();
+        // So the same cache always gives the same string
+        lines.sort();
+        return lines.into_iter().map(|line| line + "\n").collect();
+    }
+
+    pub fn load_from_string(s: &str) -> Result<SimulationCache, String> {
+        let mut cache = SimulationCache::new();
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            let (strategy, session, cached) = Self::parse_line(line)?;
+            let engine = ClueEngine::load_from_string(session)?;
+            if cached.simulation_data.values().any(|counts| counts.len() != engine.player_data.len()) {
+                return Err(format!("Wrong number of players in simulation cache line \"{}\"", line));
+            }
+            // Store it under the canonical string even if it wasn't written that way.
+            cache.entries.insert((engine.write_to_canonical_string(), strategy), cached);
+        }
+        return Ok(cache);
+    }
+
+    // Like load_from_string(), but only keeps the lines for this position and
+    // strategy.  Since we only compare against the engine's canonical string,
+    // this doesn't have to load any other engines.
+    pub fn load_entry_from_string(self: &mut Self, s: &str, engine: &ClueEngine, strategy: SamplingStrategy) -> Result<(), String> {
+        let canonical_string = engine.write_to_canonical_string();
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            let (line_strategy, session, cached) = Self::parse_line(line)?;
+            if line_strategy != strategy || session != canonical_string {
+                continue;
+            }
+            if cached.simulation_data.values().any(|counts| counts.len() != engine.player_data.len()) {
+                return Err(format!("Wrong number of players in simulation cache line \"{}\"", line));
+            }
+            self.entries.insert((canonical_string.clone(), strategy), cached);
+        }
+        return Ok(());
+    }
+
+    // A name for a file to keep the results for just this position and
+    // strategy in.  Canonical strings can be too long for a file name, so
+    // this uses a hash of it (FNV-1a, so it's the same from run to run).
+    pub fn file_name(engine: &ClueEngine, strategy: SamplingStrategy) -> String {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in engine.write_to_canonical_string().bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        return format!("{:?}-{:016x}.txt", strategy, hash);
+    }
+
+    fn parse_line(line: &str) -> Result<(SamplingStrategy, &str, CachedSimulation), String> {
+        let parts = line.split_whitespace().collect::<Vec<&str>>();
+        if parts.len() != 4 {
+            return Err(format!("Expected 4 parts in simulation cache line \"{}\"", line));
+        }
+        let strategy = SamplingStrategy::from_name(parts[0])?;
+        let total_number_of_simulations = parts[2].parse::<i32>().map_err(|_| format!("Couldn't parse number of simulations \"{}\"", parts[2]))?;
+        let card_counts = parts[3].split(';').collect::<Vec<&str>>();
+        if card_counts.len() != CardUtils::all_cards().count() {
+            return Err(format!("Wrong number of cards in simulation cache line \"{}\"", line));
+        }
+        let mut simulation_data = SimulationData::new();
+        for (card, counts_str) in CardUtils::all_cards().zip(card_counts.iter()) {
+            let counts = counts_str.split(',').map(|count| count.parse::<usize>().map_err(|_| format!("Couldn't parse count \"{}\"", count))).collect::<Result<Vec<usize>, String>>()?;
+            simulation_data.insert(card, counts);
+        }
+        return Ok((strategy, parts[1], CachedSimulation { simulation_data, total_number_of_simulations }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation_cache::*;
+
+    fn small_config() -> SimulationConfig {
+        return SimulationConfig::builder().strategy(SamplingStrategy::RandomSolutions).num_simulations(200).min_accepted_simulations(1).parallel(false).build().unwrap();
+    }
+
+    #[test]
+    fn test_equivalent_positions_share_entry() {
+        let engine1 = ClueEngine::load_from_string("63A-.3-A.3-A-BC-DE.3-A.3-A.3-A.3-A.").unwrap();
+        let engine2 = ClueEngine::load_from_string("63A-.3-A.3-A-DE-BC.3-A.3-A.3-A.3-A.").unwrap();
+        assert_eq!(engine1.write_to_canonical_string(), engine2.write_to_canonical_string());
+        let mut cache = SimulationCache::new();
+        let first = cache.simulate(&engine1, &small_config());
+        assert_eq!(1, cache.len());
+        // Enough deals already, so we get the same thing back
+        assert_eq!(first, cache.simulate(&engine2, &small_config()));
+        assert_eq!(Some(first), cache.get(&engine2, SamplingStrategy::RandomSolutions));
+        assert_eq!(None, cache.get(&engine2, SamplingStrategy::FixedSolutions));
+    }
+
+    #[test]
+    fn test_refine_adds_to_results() {
+        let engine = ClueEngine::load_from_string("63A-.3-A.3-A.3-A.3-A.3-A.3-A.").unwrap();
+        let mut cache = SimulationCache::new();
+        let (first_data, first_total) = cache.refine(&engine, &small_config());
+        let (second_data, second_total) = cache.refine(&engine, &small_config());
+        assert_eq!(200, first_total);
+        assert_eq!(400, second_total);
+        let first_plum = first_data.get(&Card::ProfessorPlum).unwrap()[0];
+        let second_plum = second_data.get(&Card::ProfessorPlum).unwrap()[0];
+        assert!(second_plum >= first_plum);
+        assert_eq!(1, cache.len());
+    }
+
+    #[test]
+    fn test_write_and_load() {
+        let engine = ClueEngine::load_from_string("63A-.3-A.3-A-BC-DE.3-A.3-A.3-A.3-A.").unwrap();
+        let mut cache = SimulationCache::new();
+        cache.refine(&engine, &small_config());
+        let loaded = SimulationCache::load_from_string(&cache.write_to_string()).unwrap();
+        assert_eq!(cache, loaded);
+        assert!(SimulationCache::load_from_string("NotAStrategy 63-.3-.3-.3-.3-.3-.3-. 0 0").is_err());
+        assert!(SimulationCache::load_from_string("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_load_entry_only_keeps_matching_position() {
+        let engine1 = ClueEngine::load_from_string("63A-.3-A.3-A-BC-DE.3-A.3-A.3-A.3-A.").unwrap();
+        let engine2 = ClueEngine::load_from_string("63A-.3-A.3-A.3-A.3-A.3-A.3-A.").unwrap();
+        let mut cache = SimulationCache::new();
+        cache.refine(&engine1, &small_config());
+        cache.refine(&engine2, &small_config());
+        let contents = cache.write_to_string();
+
+        let mut loaded = SimulationCache::new();
+        loaded.load_entry_from_string(&contents, &engine1, SamplingStrategy::RandomSolutions).unwrap();
+        assert_eq!(1, loaded.len());
+        assert_eq!(cache.get(&engine1, SamplingStrategy::RandomSolutions), loaded.get(&engine1, SamplingStrategy::RandomSolutions));
+        assert!(loaded.has_enough_simulations(&engine1, &small_config()));
+        assert!(!loaded.has_enough_simulations(&engine2, &small_config()));
+        loaded.load_entry_from_string(&contents, &engine1, SamplingStrategy::FixedSolutions).unwrap();
+        assert_eq!(1, loaded.len());
+    }
+
+    #[test]
+    fn test_file_name() {
+        let engine1 = ClueEngine::load_from_string("63A-.3-A.3-A-BC-DE.3-A.3-A.3-A.3-A.").unwrap();
+        let engine2 = ClueEngine::load_from_string("63A-.3-A.3-A-DE-BC.3-A.3-A.3-A.3-A.").unwrap();
+        let engine3 = ClueEngine::load_from_string("63A-.3-A.3-A.3-A.3-A.3-A.3-A.").unwrap();
+        assert_eq!(SimulationCache::file_name(&engine1, SamplingStrategy::RandomSolutions), SimulationCache::file_name(&engine2, SamplingStrategy::RandomSolutions));
+        assert_ne!(SimulationCache::file_name(&engine1, SamplingStrategy::RandomSolutions), SimulationCache::file_name(&engine3, SamplingStrategy::RandomSolutions));
+        assert_ne!(SimulationCache::file_name(&engine1, SamplingStrategy::RandomSolutions), SimulationCache::file_name(&engine1, SamplingStrategy::FixedSolutions));
+    }
+}
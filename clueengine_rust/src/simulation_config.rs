@@ -0,0 +1,190 @@
+// Settings for ClueEngine::do_simulation_with_config().  Make one with
+// SimulationConfig::builder(), or use SimulationConfig::default() to get the
+// same thing do_simulation(false) does.
+
+use crate::SamplingStrategy;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulationConfig {
+    strategy: SamplingStrategy,
+    num_simulations: i32,
+    chunk_size: i32,
+    max_iterations: i32,
+    min_accepted_simulations: usize,
+    parallel: bool,
+    num_threads: Option<usize>,
+}
+
+impl SimulationConfig {
+    pub fn builder() -> SimulationConfigBuilder {
+        return SimulationConfigBuilder::new();
+    }
+
+    pub fn strategy(self: &Self) -> SamplingStrategy {
+        return self.strategy;
+    }
+
+    // How many deals to try each round.  For SamplingStrategy::FixedSolutions
+    // these are split evenly between the possible solutions.
+    pub fn num_simulations(self: &Self) -> i32 {
+        return self.num_simulations;
+    }
+
+    // When running in parallel, each task tries at most this many deals.
+    pub fn chunk_size(self: &Self) -> i32 {
+        return self.chunk_size;
+    }
+
+    // The most rounds of SamplingStrategy::FixedSolutions to do before
+    // giving up on getting min_accepted_simulations() consistent deals.
+    pub fn max_iterations(self: &Self) -> i32 {
+        return self.max_iterations;
+    }
+
+    // SamplingStrategy::FixedSolutions keeps doing rounds until it has at
+    // least this many consistent deals.
+    pub fn min_accepted_simulations(self: &Self) -> usize {
+        return self.min_accepted_simulations;
+    }
+
+    pub fn parallel(self: &Self) -> bool {
+        return self.parallel;
+    }
+
+    // None means use rayon's global thread pool.
+    pub fn num_threads(self: &Self) -> Option<usize> {
+        return self.num_threads;
+    }
+}
+
+impl Default for SimulationConfig {
+    fn default() -> SimulationConfig {
+        return SimulationConfig::builder().build().unwrap();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SimulationConfigBuilder {
+    strategy: SamplingStrategy,
+    // None means pick based on the strategy
+    num_simulations: Option<i32>,
+    chunk_size: i32,
+    max_iterations: i32,
+    min_accepted_simulations: usize,
+    parallel: bool,
+    num_threads: Option<usize>,
+}
+
+impl SimulationConfigBuilder {
+    pub fn new() -> SimulationConfigBuilder {
+        return SimulationConfigBuilder {
+            strategy: SamplingStrategy::FixedSolutions,
+            num_simulations: None,
+            chunk_size: 1000,
+            max_iterations: 100,
+            min_accepted_simulations: 1000,
+            parallel: true,
+            num_threads: None,
+        };
+    }
+
+    pub fn strategy(mut self: Self, strategy: SamplingStrategy) -> SimulationConfigBuilder {
+        self.strategy = strategy;
+        return self;
+    }
+
+    pub fn num_simulations(mut self: Self, num_simulations: i32) -> SimulationConfigBuilder {
+        self.num_simulations = Some(num_simulations);
+        return self;
+    }
+
+    pub fn chunk_size(mut self: Self, chunk_size: i32) -> SimulationConfigBuilder {
+        self.chunk_size = chunk_size;
+        return self;
+    }
+
+    pub fn max_iterations(mut self: Self, max_iterations: i32) -> SimulationConfigBuilder {
+        self.max_iterations = max_iterations;
+        return self;
+    }
+
+    pub fn min_accepted_simulations(mut self: Self, min_accepted_simulations: usize) -> SimulationConfigBuilder {
+        self.min_accepted_simulations = min_accepted_simulations;
+        return self;
+    }
+
+    pub fn parallel(mut self: Self, parallel: bool) -> SimulationConfigBuilder {
+        self.parallel = parallel;
+        return self;
+    }
+
+    pub fn num_threads(mut self: Self, num_threads: usize) -> SimulationConfigBuilder {
+        self.num_threads = Some(num_threads);
+        return self;
+    }
+
+    pub fn build(self: Self) -> Result<SimulationConfig, String> {
+        // Picking a solution for every deal means many more deals are thrown
+        // out, so do more of them.
+        let default_num_simulations = if self.strategy == SamplingStrategy::RandomSolutions { 100000 } else { 20000 };
+        let num_simulations = self.num_simulations.unwrap_or(default_num_simulations);
+        if num_simulations <= 0 {
+            return Err(format!("num_simulations must be positive (got {})", num_simulations));
+        }
+        if self.chunk_size <= 0 {
+            return Err(format!("chunk_size must be positive (got {})", self.chunk_size));
+        }
+        if self.max_iterations <= 0 {
+            return Err(format!("max_iterations must be positive (got {})", self.max_iterations));
+        }
+        if self.num_threads == Some(0) {
+            return Err(String::from("num_threads must be positive"));
+        }
+        return Ok(SimulationConfig {
+            strategy: self.strategy,
+            num_simulations,
+            chunk_size: self.chunk_size,
+            max_iterations: self.max_iterations,
+            min_accepted_simulations: self.min_accepted_simulations,
+            parallel: self.parallel,
+            num_threads: self.num_threads,
+        });
+    }
+}
+
+impl Default for SimulationConfigBuilder {
+    fn default() -> SimulationConfigBuilder {
+        return SimulationConfigBuilder::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation_config::*;
+
+    #[test]
+    fn test_default_matches_do_simulation() {
+        let config = SimulationConfig::default();
+        assert_eq!(SamplingStrategy::FixedSolutions, config.strategy());
+        assert_eq!(20000, config.num_simulations());
+        assert_eq!(1000, config.chunk_size());
+        assert_eq!(100, config.max_iterations());
+        assert_eq!(1000, config.min_accepted_simulations());
+        assert!(config.parallel());
+        assert_eq!(None, config.num_threads());
+    }
+
+    #[test]
+    fn test_num_simulations_depends_on_strategy() {
+        assert_eq!(100000, SimulationConfig::builder().strategy(SamplingStrategy::RandomSolutions).build().unwrap().num_simulations());
+        assert_eq!(500, SimulationConfig::builder().strategy(SamplingStrategy::RandomSolutions).num_simulations(500).build().unwrap().num_simulations());
+    }
+
+    #[test]
+    fn test_build_rejects_bad_values() {
+        assert!(SimulationConfig::builder().num_simulations(0).build().is_err());
+        assert!(SimulationConfig::builder().chunk_size(-1).build().is_err());
+        assert!(SimulationConfig::builder().max_iterations(0).build().is_err());
+        assert!(SimulationConfig::builder().num_threads(0).build().is_err());
+    }
+}
@@ -0,0 +1,183 @@
+// What the simulations need to know about an engine, with every hand as a
+// mask of cards (see CardUtils::card_set_to_mask()).  Cloning a ClueEngine
+// means cloning all of its HashSets and clauses, which used to be most of the
+// time spent simulating, so instead we build one of these for each possible
+// solution and reuse it for every deal.
+//
+// We don't make any deductions from the solution like ClueEngine would.
+// That means more deals are thrown out, but the deals that aren't are exactly
+// the same.
+
+use crate::{Card, CardUtils, ClueEngine};
+use rand::Rng;
+
+#[derive(Clone, Debug)]
+pub struct SimulationState {
+    // One per player, with the solution last
+    known_hands: Vec<u32>,
+    forbidden_cards: Vec<u32>,
+    clause_masks: Vec<Vec<u32>>,
+    num_cards: Vec<u32>,
+    // How many more cards each real player needs to be dealt
+    num_cards_needed: Vec<usize>,
+    // Everything below is overwritten by every deal.
+    // The cards that are left to deal, in whatever order the last deal left them.
+    available_cards: Vec<Card>,
+    hands: Vec<u32>,
+}
+
+impl SimulationState {
+    // Returns None if some player doesn't have a known number of cards.
+    pub fn new(engine: &ClueEngine) -> Option<SimulationState> {
+        if engine.player_data.iter().any(|player| player.num_cards == None) {
+            return None;
+        }
+        let num_cards_needed = engine.player_data.iter().take(engine.number_of_real_players()).map(|player| {
+            // If a player already has too many cards, that's caught when
+            // checking the deal.
+            (player.num_cards.unwrap() as usize).saturating_sub(player.has_cards.len())
+        }).collect();
+        let known_hands = engine.player_data.iter().map(|player| CardUtils::card_set_to_mask(&player.has_cards)).collect::<Vec<u32>>();
+        let assigned_cards = known_hands.iter().fold(0, |mask, hand| mask | hand);
+        return Some(SimulationState {
+            known_hands,
+            forbidden_cards: engine.player_data.iter().map(|player| CardUtils::card_set_to_mask(&player.not_has_cards)).collect(),
+            clause_masks: engine.player_data.iter().map(|player| player.possible_cards.masks().to_vec()).collect(),
+            num_cards: engine.player_data.iter().map(|player| player.num_cards.unwrap() as u32).collect(),
+            num_cards_needed,
+            available_cards: CardUtils::all_cards().filter(|card| assigned_cards & (1 << (*card as u32)) == 0).collect(),
+            hands: vec![0; engine.player_data.len()],
+        });
+    }
+
+    // A copy of this where the solution is these cards.
+    pub fn with_solution(self: &Self, solution: &[Card]) -> SimulationState {
+        let mut simulation_state = self.clone();
+        let solution_mask = solution.iter().fold(0, |mask, card| mask | (1 << (*card as u32)));
+        *simulation_state.known_hands.last_mut().unwrap() |= solution_mask;
+        simulation_state.available_cards.retain(|card| solution_mask & (1 << (*card as u32)) == 0);
+        return simulation_state;
+    }
+
+    // Deals the available cards randomly to the players who need them, and
+    // returns whether the deal is consistent with everything we know.  If it
+    // is, hands() has the deal.
+    // Note that we do at least 20,000 of these, so performance is very important!
+    pub fn deal<R: Rng + ?Sized>(self: &mut Self, rng: &mut R) -> bool {
+        self.hands.copy_from_slice(&self.known_hands);
+        let num_available_cards = self.available_cards.len();
+        // This is a Fisher-Yates shuffle, but we only shuffle as far as we
+        // need to, so we can stop as soon as the deal is inconsistent.
+        let mut index = 0;
+        for player_index in 0..self.num_cards_needed.len() {
+            let num_cards_needed = self.num_cards_needed[player_index];
+            // If there are not enough cards available, we're
+            // inconsistent.
+            if num_available_cards - index < num_cards_needed {
+                return false;
+            }
+            for _ in 0..num_cards_needed {
+                let swap_index = index + random_below(rng, (num_available_cards - index) as u32) as usize;
+                self.available_cards.swap(index, swap_index);
+                let card_mask = 1 << (self.available_cards[index] as u32);
+                index += 1;
+                // see if we're going to be inconsistent and exit early
+                // This early return helps performance when we have to do a lot of simulations
+                // because there are a lot of conditions to meet
+                if self.forbidden_cards[player_index] & card_mask != 0 {
+                    return false;
+                }
+                self.hands[player_index] |= card_mask;
+            }
+        }
+        // All players assigned.  Check consistency.
+        return self.is_consistent_after_all_cards_assigned();
+    }
+
+    // One hand per player, with the solution last.
+    pub fn hands(self: &Self) -> &[u32] {
+        return &self.hands;
+    }
+
+    fn is_consistent_after_all_cards_assigned(self: &Self) -> bool {
+        let mut cards_seen: u32 = 0;
+        for (player_index, hand) in self.hands.iter().enumerate() {
+            if cards_seen & hand != 0 {
+                // Already seen this card in someone else's cards, so not consistent
+                return false;
+            }
+            cards_seen |= hand;
+            if self.forbidden_cards[player_index] & hand != 0 {
+                // We already knew this player has and doesn't have a card
+                return false;
+            }
+            if hand.count_ones() != self.num_cards[player_index] {
+                // wrong number of cards
+                return false;
+            }
+            if self.clause_masks[player_index].iter().any(|clause| clause & hand == 0) {
+                // This clause is not satisfied
+                return false;
+            }
+        }
+        return true;
+    }
+}
+
+// A uniformly random number from 0 to range - 1.  This is what
+// rng.gen_range(0, range) does, but that always does a division, which was a
+// noticeable part of each deal.  This is Lemire's method, which only needs
+// to divide once in a while.
+fn random_below<R: Rng + ?Sized>(rng: &mut R, range: u32) -> u32 {
+    let mut product = rng.next_u32() as u64 * range as u64;
+    if (product as u32) < range {
+        let threshold = range.wrapping_neg() % range;
+        while (product as u32) < threshold {
+            product = rng.next_u32() as u64 * range as u64;
+        }
+    }
+    return (product >> 32) as u32;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation_state::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_deal_is_consistent() {
+        let mut engine = ClueEngine::load_from_string("63A-.3-A.3-A.3-A.3-A.3-A.3-A.").unwrap();
+        engine.learn_suggest(0, Card::MrGreen, Card::Knife, Card::Hall, Some(1), None);
+        engine.learn_info_on_card(2, Card::Rope, false, true);
+        let solution = [Card::ColonelMustard, Card::Wrench, Card::Study];
+        let mut simulation_state = SimulationState::new(&engine).unwrap().with_solution(&solution);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut num_consistent = 0;
+        for _ in 0..1000 {
+            if simulation_state.deal(&mut rng) {
+                num_consistent += 1;
+                let hands = simulation_state.hands();
+                let hand_cards = hands.iter().map(|hand| CardUtils::cards_in_mask(*hand).collect::<Vec<Card>>()).collect::<Vec<Vec<Card>>>();
+                assert_eq!(solution.to_vec(), hand_cards[6]);
+                assert!(hand_cards[0].contains(&Card::ProfessorPlum));
+                assert!(hand_cards[1].iter().any(|card| [Card::MrGreen, Card::Knife, Card::Hall].contains(card)));
+                assert!(!hand_cards[2].contains(&Card::Rope));
+                for (player_index, hand) in hands.iter().enumerate() {
+                    assert_eq!(engine.player_data[player_index].num_cards.unwrap() as u32, hand.count_ones());
+                }
+                assert_eq!((1 << 21) - 1, hands.iter().fold(0, |mask, hand| mask | hand));
+            }
+        }
+        assert!(num_consistent > 0);
+    }
+
+    #[test]
+    fn test_random_below() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut counts = [0; 5];
+        for _ in 0..5000 {
+            counts[random_below(&mut rng, 5) as usize] += 1;
+        }
+        assert!(counts.iter().all(|count| *count > 800 && *count < 1200));
+    }
+}
@@ -0,0 +1,230 @@
+// Weak evidence about players' hands from what they suggest.
+//
+// Some players like to suggest cards they have (to throw everyone off), and
+// some keep suggesting cards they don't have until someone shows them one.
+// Neither tells us anything for certain, so none of this ever changes what a
+// ClueEngine knows.  Instead, each consistent deal is weighted by how well it
+// fits the suggestions: every time a player suggested a card they have in
+// the deal, the deal's weight is multiplied by that player's
+// held_card_weight.  A weight above 1 means the player tends to bluff, below
+// 1 means they tend to ask about cards they don't have, and 1 means their
+// suggestions tell us nothing.
+//
+// Refutations where we saw the card are weighted too.  The engine just
+// learns that the refuting player has the card, but if they had other
+// suggested cards as well they might have shown one of those instead, so
+// deals where they only had the one card fit better.  How likely each choice
+// is comes from a RefutationModel, which knows players by name (see
+// set_player_names()).  Refutations where we didn't see the card don't need
+// weighting: the player always shows something, so whatever their habits
+// are, every deal where they could refute fits equally well.
+
+use crate::{Card, CardUtils, ClueEngine, Deal, DealCounter, RefutationModel};
+use rand::Rng;
+use std::collections::HashMap;
+
+// Like SimulationData, but with the (weighted) probability each player has
+// each card.
+pub type WeightedSimulationData = HashMap<Card, Vec<f64>>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoftEvidence {
+    default_held_card_weight: f64,
+    player_held_card_weights: HashMap<usize, f64>,
+    // (suggesting player, cards)
+    suggestions: Vec<(usize, [Card; 3])>,
+    refutation_model: RefutationModel,
+    // Indexed by player, for looking them up in refutation_model
+    player_names: Vec<String>,
+    // (refuting player, suggested cards, card shown)
+    refutations: Vec<(usize, [Card; 3], Card)>,
+}
+
+impl SoftEvidence {
+    pub fn new(default_held_card_weight: f64) -> Result<SoftEvidence, String> {
+        SoftEvidence::check_weight(default_held_card_weight)?;
+        return Ok(SoftEvidence {
+            default_held_card_weight,
+            player_held_card_weights: HashMap::new(),
+            suggestions: vec![],
+            refutation_model: RefutationModel::uniform(),
+            player_names: vec![],
+            refutations: vec![],
+        });
+    }
+
+    fn check_weight(weight: f64) -> Result<(), String> {
+        if !(weight.is_finite() && weight > 0.0) {
+            return Err(format!("Weights must be positive (got {})", weight));
+        }
+        return Ok(());
+    }
+
+    // Use a different weight for a player we know more about.
+    pub fn set_held_card_weight(self: &mut Self, player_index: usize, weight: f64) -> Result<(), String> {
+        SoftEvidence::check_weight(weight)?;
+        self.player_held_card_weights.insert(player_index, weight);
+        return Ok(());
+    }
+
+    pub fn held_card_weight(self: &Self, player_index: usize) -> f64 {
+        return *self.player_held_card_weights.get(&player_index).unwrap_or(&self.default_held_card_weight);
+    }
+
+    pub fn record_suggestion(self: &mut Self, suggesting_player_index: usize, card1: Card, card2: Card, card3: Card) {
+        self.suggestions.push((suggesting_player_index, [card1, card2, card3]));
+    }
+
+    pub fn num_suggestions(self: &Self) -> usize {
+        return self.suggestions.len();
+    }
+
+    // By default every card is equally likely to be shown.
+    pub fn set_refutation_model(self: &mut Self, refutation_model: RefutationModel) {
+        self.refutation_model = refutation_model;
+    }
+
+    // Who's sitting in each seat this game, so the refutation model can use
+    // their habits.  Players without a name get everyone's habits.
+    pub fn set_player_names(self: &mut Self, player_names: Vec<String>) {
+        self.player_names = player_names;
+    }
+
+    // Refutations where card_shown is None are ignored (see above).
+    pub fn record_refutation(self: &mut Self, refuting_player_index: usize, card1: Card, card2: Card, card3: Card, card_shown: Option<Card>) {
+        if let Some(card_shown) = card_shown {
+            self.refutations.push((refuting_player_index, [card1, card2, card3], card_shown));
+        }
+    }
+
+    // How much more (or less) likely this deal is because of the suggestions.
+    pub fn deal_weight(self: &Self, deal: &Deal) -> f64 {
+        let mut weight = 1.0;
+        for (player_index, cards) in self.suggestions.iter() {
+            for card in cards.iter() {
+                if deal.hands[*player_index].contains(card) {
+                    weight *= self.held_card_weight(*player_index);
+                }
+            }
+        }
+        for (player_index, cards, card_shown) in self.refutations.iter() {
+            let held_cards = cards.iter().copied().filter(|card| deal.hands[*player_index].contains(card)).collect::<Vec<Card>>();
+            let player_name = self.player_names.get(*player_index).map(|name| name.as_str());
+            weight *= self.refutation_model.show_probability(player_name, &held_cards, *card_shown);
+        }
+        return weight;
+    }
+
+    // Samples num_deals deals consistent with what engine knows and returns
+    // the probability each player has each card, with each deal weighted by
+    // deal_weight().  Cards whose owner is known keep probability 1.  Returns
+    // None if no consistent deals were found.
+    pub fn weighted_probabilities<R: Rng>(self: &Self, engine: &ClueEngine, num_deals: usize, mut rng: R) -> Option<WeightedSimulationData> {
+        // Sample uniformly if we can, so the only bias is from the weights.
+        let deals = match DealCounter::new(engine) {
+            Ok(mut deal_counter) => (0..num_deals).filter_map(|_| deal_counter.sample(&mut rng)).collect::<Vec<Deal>>(),
+            Err(_) => engine.sample_deals(num_deals, rng).collect::<Vec<Deal>>()
+        };
+        let num_players = engine.player_data.len();
+        let mut weights: WeightedSimulationData = CardUtils::all_cards().map(|card| (card, vec![0.0; num_players])).collect();
+        let mut total_weight = 0.0;
+        for deal in deals.iter() {
+            let weight = self.deal_weight(deal);
+            for (player_index, hand) in deal.hands.iter().enumerate() {
+                for card in hand.iter() {
+                    weights.get_mut(card).unwrap()[player_index] += weight;
+                }
+            }
+            total_weight += weight;
+        }
+        if total_weight == 0.0 {
+            return None;
+        }
+        for player_weights in weights.values_mut() {
+            for weight in player_weights.iter_mut() {
+                *weight /= total_weight;
+            }
+        }
+        return Some(weights);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::soft_evidence::*;
+    use crate::RefutationObservation;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_bad_weights() {
+        assert!(SoftEvidence::new(0.0).is_err());
+        assert!(SoftEvidence::new(f64::NAN).is_err());
+        let mut evidence = SoftEvidence::new(1.0).unwrap();
+        assert!(evidence.set_held_card_weight(1, -2.0).is_err());
+        evidence.set_held_card_weight(1, 2.0).unwrap();
+        assert_eq!(2.0, evidence.held_card_weight(1));
+        assert_eq!(1.0, evidence.held_card_weight(2));
+    }
+
+    #[test]
+    fn test_deal_weight() {
+        let mut evidence = SoftEvidence::new(3.0).unwrap();
+        evidence.record_suggestion(0, Card::ProfessorPlum, Card::Knife, Card::Hall);
+        evidence.record_suggestion(0, Card::ProfessorPlum, Card::Rope, Card::Hall);
+        let deal = Deal { hands: vec![
+            [Card::ProfessorPlum, Card::Rope].iter().copied().collect(),
+            [Card::Knife].iter().copied().collect(),
+            [Card::Hall].iter().copied().collect(),
+        ] };
+        // Plum twice and the Rope once
+        assert_eq!(27.0, evidence.deal_weight(&deal));
+    }
+
+    #[test]
+    fn test_refutation_weight() {
+        let mut evidence = SoftEvidence::new(1.0).unwrap();
+        evidence.record_refutation(0, Card::ProfessorPlum, Card::Knife, Card::Hall, Some(Card::Hall));
+        // Ignored
+        evidence.record_refutation(1, Card::ProfessorPlum, Card::Knife, Card::Hall, None);
+        let deal_with_choice = Deal { hands: vec![
+            [Card::ProfessorPlum, Card::Hall].iter().copied().collect(),
+            [Card::Knife].iter().copied().collect(),
+        ] };
+        let deal_without_choice = Deal { hands: vec![
+            [Card::Hall].iter().copied().collect(),
+            [Card::ProfessorPlum, Card::Knife].iter().copied().collect(),
+        ] };
+        assert_eq!(0.5, evidence.deal_weight(&deal_with_choice));
+        assert_eq!(1.0, evidence.deal_weight(&deal_without_choice));
+
+        // A player who always shows rooms gives away nothing about Professor Plum by showing the Hall
+        let observations = (0..50).map(|_| RefutationObservation { player: String::from("Alice"), held_cards: vec![Card::MrGreen, Card::Lounge], card_shown: Card::Lounge }).collect::<Vec<RefutationObservation>>();
+        evidence.set_refutation_model(RefutationModel::learn(&observations));
+        evidence.set_player_names(vec![String::from("Alice"), String::from("Bob")]);
+        assert!(evidence.deal_weight(&deal_with_choice) > 0.95);
+        // Alice's habits don't follow her seat
+        evidence.set_player_names(vec![String::from("Bob"), String::from("Alice")]);
+        let bob_weight = evidence.deal_weight(&deal_with_choice);
+        evidence.set_player_names(vec![]);
+        assert_eq!(bob_weight, evidence.deal_weight(&deal_with_choice));
+    }
+
+    #[test]
+    fn test_bluffing_makes_suggested_cards_likelier() {
+        let mut engine = ClueEngine::new(3, None).unwrap();
+        engine.learn_info_on_card(2, Card::Wrench, true, true);
+        let mut evidence = SoftEvidence::new(1.0).unwrap();
+        for _ in 0..3 {
+            evidence.record_suggestion(1, Card::ProfessorPlum, Card::Knife, Card::Hall);
+        }
+        let neutral = evidence.weighted_probabilities(&engine, 2000, StdRng::seed_from_u64(1)).unwrap();
+        evidence.set_held_card_weight(1, 4.0).unwrap();
+        let bluffing = evidence.weighted_probabilities(&engine, 2000, StdRng::seed_from_u64(1)).unwrap();
+        assert!(bluffing[&Card::Knife][1] > neutral[&Card::Knife][1] + 0.1);
+        // Known facts don't change
+        assert_eq!(1.0, bluffing[&Card::Wrench][2]);
+        assert_eq!(0.0, bluffing[&Card::Wrench][1]);
+        let total = bluffing[&Card::Knife].iter().sum::<f64>();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,74 @@
+// How likely each possible solution is, usually from
+// ClueEngine::simulate_solutions().
+
+use crate::Card;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SolutionDistribution {
+    // Each solution is (suspect, weapon, room).  Sorted with the most likely
+    // first, and the probabilities add up to 1 (unless this is empty).
+    solutions: Vec<([Card; 3], f64)>,
+}
+
+impl SolutionDistribution {
+    // counts don't have to be normalized.  Solutions that came up 0 times
+    // are left out.
+    pub fn from_counts<I: IntoIterator<Item=([Card; 3], usize)>>(counts: I) -> SolutionDistribution {
+        let counts = counts.into_iter().filter(|(_, count)| *count > 0).collect::<Vec<([Card; 3], usize)>>();
+        let total = counts.iter().map(|(_, count)| count).sum::<usize>() as f64;
+        let mut solutions = counts.into_iter().map(|(solution, count)| (solution, count as f64 / total)).collect::<Vec<([Card; 3], f64)>>();
+        // Break ties by solution so this doesn't depend on the order of counts.
+        solutions.sort_by(|(solution1, probability1), (solution2, probability2)| probability2.partial_cmp(probability1).unwrap().then_with(|| solution1.cmp(solution2)));
+        return SolutionDistribution { solutions };
+    }
+
+    // Empty if there were no consistent deals.
+    pub fn solutions(self: &Self) -> &[([Card; 3], f64)] {
+        return &self.solutions;
+    }
+
+    pub fn is_empty(self: &Self) -> bool {
+        return self.solutions.is_empty();
+    }
+
+    pub fn probability(self: &Self, solution: &[Card; 3]) -> f64 {
+        return self.solutions.iter().find(|(other_solution, _)| other_solution == solution).map_or(0.0, |(_, probability)| *probability);
+    }
+
+    // The best accusation to make, and how likely it is to be right.
+    pub fn most_likely(self: &Self) -> Option<([Card; 3], f64)> {
+        return self.solutions.first().copied();
+    }
+
+    // In bits.  0 means the solution is certain.
+    pub fn entropy(self: &Self) -> f64 {
+        return -self.solutions.iter().map(|(_, probability)| probability * probability.log2()).sum::<f64>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solution_distribution::*;
+
+    #[test]
+    fn test_from_counts() {
+        let plum = [Card::ProfessorPlum, Card::Knife, Card::Hall];
+        let green = [Card::MrGreen, Card::Knife, Card::Hall];
+        let scarlet = [Card::MissScarlet, Card::Knife, Card::Hall];
+        let distribution = SolutionDistribution::from_counts(vec![(green, 1), (plum, 3), (scarlet, 0)]);
+        assert_eq!(Some((plum, 0.75)), distribution.most_likely());
+        assert_eq!(2, distribution.solutions().len());
+        assert_eq!(0.25, distribution.probability(&green));
+        assert_eq!(0.0, distribution.probability(&scarlet));
+        let expected_entropy = -(0.75f64 * 0.75f64.log2() + 0.25 * 0.25f64.log2());
+        assert!((distribution.entropy() - expected_entropy).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_empty() {
+        let distribution = SolutionDistribution::from_counts(vec![]);
+        assert!(distribution.is_empty());
+        assert_eq!(None, distribution.most_likely());
+        assert_eq!(0.0, distribution.entropy());
+    }
+}
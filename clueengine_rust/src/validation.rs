@@ -0,0 +1,151 @@
+// Checks for everything that can't be true about an engine.  See
+// ClueEngine::validate().
+//
+// is_consistent() only catches a player who both has and doesn't have a
+// card, which is what learning contradictory things usually leads to.  A
+// session string from somewhere else can say all sorts of impossible things
+// without tripping that, so this looks at everything.
+
+use crate::{Card, CardType, CardUtils, ClueEngine, CARD_LAST};
+use std::fmt;
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Violation {
+    HasAndNotHas { player_index: usize, card: Card },
+    // More than one player (possibly including the solution) has the card.
+    MultipleOwners { card: Card, player_indices: Vec<usize> },
+    // Every player (and the solution) doesn't have the card.
+    NoPossibleOwner { card: Card },
+    // The player has more cards than they were dealt.
+    TooManyCards { player_index: usize, num_cards: u8, num_known: usize },
+    // The player doesn't have enough cards left that they could have.
+    TooFewPossibleCards { player_index: usize, num_cards: u8, num_possible: usize },
+    MultipleSolutionCards { card_type: CardType, cards: Vec<Card> },
+    NoPossibleSolutionCard { card_type: CardType },
+    // The real players' hands don't add up to the cards that aren't in the
+    // solution.  Only checked if we know every hand size.
+    WrongTotalHandSize { total: usize, expected: usize },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(self: &Self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Violation::HasAndNotHas { player_index, card } =>
+                write!(f, "Player {} both has and doesn't have {:?}", player_index, card),
+            Violation::MultipleOwners { card, player_indices } =>
+                write!(f, "{:?} is owned by more than one player: {:?}", card, player_indices),
+            Violation::NoPossibleOwner { card } =>
+                write!(f, "No one can have {:?}", card),
+            Violation::TooManyCards { player_index, num_cards, num_known } =>
+                write!(f, "Player {} has {} cards but only has {} cards in their hand", player_index, num_known, num_cards),
+            Violation::TooFewPossibleCards { player_index, num_cards, num_possible } =>
+                write!(f, "Player {} has {} cards in their hand but can only have {} cards", player_index, num_cards, num_possible),
+            Violation::MultipleSolutionCards { card_type, cards } =>
+                write!(f, "The solution has more than one {:?}: {:?}", card_type, cards),
+            Violation::NoPossibleSolutionCard { card_type } =>
+                write!(f, "The solution can't have any {:?}", card_type),
+            Violation::WrongTotalHandSize { total, expected } =>
+                write!(f, "Players have {} cards in total (expected {})", total, expected),
+        };
+    }
+}
+
+// Every violation, in a predictable order.
+pub(crate) fn find_violations(engine: &ClueEngine) -> Vec<Violation> {
+    let mut violations = vec![];
+    for (player_index, player) in engine.player_data.iter().enumerate() {
+        for card in CardUtils::all_cards() {
+            if player.has_cards.contains(&card) && player.not_has_cards.contains(&card) {
+                violations.push(Violation::HasAndNotHas { player_index, card });
+            }
+        }
+    }
+    for card in CardUtils::all_cards() {
+        let owners = engine.player_data.iter().enumerate()
+            .filter(|(_, player)| player.has_cards.contains(&card))
+            .map(|(player_index, _)| player_index)
+            .collect::<Vec<usize>>();
+        if owners.len() > 1 {
+            violations.push(Violation::MultipleOwners { card, player_indices: owners });
+        }
+        if engine.player_data.iter().all(|player| player.not_has_cards.contains(&card) && !player.has_cards.contains(&card)) {
+            violations.push(Violation::NoPossibleOwner { card });
+        }
+    }
+    for (player_index, player) in engine.player_data.iter().enumerate() {
+        if let Some(num_cards) = player.num_cards {
+            let num_known = player.has_cards.len();
+            if num_known > num_cards as usize {
+                violations.push(Violation::TooManyCards { player_index, num_cards, num_known });
+            }
+            let num_possible = CardUtils::all_cards().filter(|card| !player.not_has_cards.contains(card) || player.has_cards.contains(card)).count();
+            if num_possible < num_cards as usize {
+                violations.push(Violation::TooFewPossibleCards { player_index, num_cards, num_possible });
+            }
+        }
+    }
+    let solution = &engine.player_data[engine.number_of_real_players()];
+    for card_type in CardUtils::all_card_types() {
+        let mut cards = CardUtils::cards_of_type(*card_type).filter(|card| solution.has_cards.contains(card)).collect::<Vec<Card>>();
+        cards.sort();
+        if cards.len() > 1 {
+            violations.push(Violation::MultipleSolutionCards { card_type: *card_type, cards });
+        }
+        if CardUtils::cards_of_type(*card_type).all(|card| solution.not_has_cards.contains(&card) && !solution.has_cards.contains(&card)) {
+            violations.push(Violation::NoPossibleSolutionCard { card_type: *card_type });
+        }
+    }
+    let hand_sizes = engine.player_data[..engine.number_of_real_players()].iter().map(|player| player.num_cards).collect::<Option<Vec<u8>>>();
+    if let Some(hand_sizes) = hand_sizes {
+        let total = hand_sizes.iter().map(|num_cards| *num_cards as usize).sum::<usize>();
+        let expected = CARD_LAST as usize - 3;
+        if total != expected {
+            violations.push(Violation::WrongTotalHandSize { total, expected });
+        }
+    }
+    return violations;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::validation::*;
+
+    #[test]
+    fn test_new_engine_is_valid() {
+        let engine = ClueEngine::new(4, None).unwrap();
+        assert_eq!(Vec::<Violation>::new(), find_violations(&engine));
+    }
+
+    #[test]
+    fn test_finds_each_kind_of_violation() {
+        let mut engine = ClueEngine::new(3, None).unwrap();
+        // Set these up directly, since learning them would deduce other things.
+        engine.player_data[0].has_cards.insert(Card::Knife);
+        engine.player_data[1].has_cards.insert(Card::Knife);
+        engine.player_data[1].not_has_cards.insert(Card::Knife);
+        for player in engine.player_data.iter_mut() {
+            player.not_has_cards.insert(Card::Hall);
+        }
+        engine.player_data[2].num_cards = Some(1);
+        engine.player_data[2].has_cards.extend([Card::Rope, Card::Wrench]);
+        engine.player_data[3].has_cards.extend([Card::ProfessorPlum, Card::MrGreen]);
+        let violations = find_violations(&engine);
+        assert_eq!(vec![
+            Violation::HasAndNotHas { player_index: 1, card: Card::Knife },
+            Violation::MultipleOwners { card: Card::Knife, player_indices: vec![0, 1] },
+            Violation::NoPossibleOwner { card: Card::Hall },
+            Violation::TooManyCards { player_index: 2, num_cards: 1, num_known: 2 },
+            Violation::MultipleSolutionCards { card_type: CardType::Suspect, cards: vec![Card::ProfessorPlum, Card::MrGreen] },
+            Violation::WrongTotalHandSize { total: 13, expected: 18 },
+        ], violations);
+    }
+
+    #[test]
+    fn test_too_few_possible_cards() {
+        let mut engine = ClueEngine::new(3, None).unwrap();
+        for card in CardUtils::all_cards().skip(5) {
+            engine.player_data[0].not_has_cards.insert(card);
+        }
+        assert_eq!(vec![Violation::TooFewPossibleCards { player_index: 0, num_cards: 6, num_possible: 5 }], find_violations(&engine));
+    }
+}
@@ -0,0 +1,90 @@
+// Answers to "what would we learn if..." questions, without changing the
+// engine.  See ClueEngine::what_if_suggest() and
+// ClueEngine::what_if_all_suggestion_responses().
+
+use crate::{Card, CardSet, ChangeSet, ClueEngine, Fact};
+
+// One way a suggestion could go.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum SuggestionResponse {
+    // No one could show a card.
+    NotRefuted,
+    // card_shown is None if we didn't get to see which card it was.
+    Refuted { player_index: usize, card_shown: Option<Card> },
+}
+
+impl SuggestionResponse {
+    pub fn refuting_player_index(self: &Self) -> Option<usize> {
+        return match self {
+            SuggestionResponse::NotRefuted => None,
+            SuggestionResponse::Refuted { player_index, .. } => Some(*player_index),
+        };
+    }
+
+    pub fn card_shown(self: &Self) -> Option<Card> {
+        return match self {
+            SuggestionResponse::NotRefuted => None,
+            SuggestionResponse::Refuted { card_shown, .. } => *card_shown,
+        };
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct WhatIfResult {
+    // Everything we'd newly know about who has which card, sorted by player
+    // and then card.
+    pub new_facts: Vec<Fact>,
+    // The cards that would be newly known to be in (or not in) the solution.
+    // These are also in new_facts.
+    pub solution_changes: Vec<Fact>,
+    // The cards we'd learn something new about (see ChangeSet::cards()).
+    pub changed_cards: CardSet,
+    // If this is false, what we asked about can't happen.
+    pub is_consistent: bool,
+}
+
+impl WhatIfResult {
+    // Builds the result from what the engine learned.
+    pub(crate) fn from_change_set(after: &ClueEngine, change_set: &ChangeSet) -> WhatIfResult {
+        let mut new_facts = change_set.new_facts.clone();
+        new_facts.sort_by_key(|fact| (fact.player_index, fact.card));
+        let solution_player_index = after.number_of_real_players();
+        let solution_changes = new_facts.iter().filter(|fact| fact.player_index == solution_player_index).copied().collect();
+        return WhatIfResult {
+            new_facts,
+            solution_changes,
+            changed_cards: change_set.cards(),
+            is_consistent: after.is_consistent(),
+        };
+    }
+
+    // Whether we'd learn anything at all.
+    pub fn learns_anything(self: &Self) -> bool {
+        return !self.new_facts.is_empty();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::what_if::*;
+
+    #[test]
+    fn test_response_accessors() {
+        assert_eq!(None, SuggestionResponse::NotRefuted.refuting_player_index());
+        assert_eq!(None, SuggestionResponse::NotRefuted.card_shown());
+        let response = SuggestionResponse::Refuted { player_index: 2, card_shown: Some(Card::Knife) };
+        assert_eq!(Some(2), response.refuting_player_index());
+        assert_eq!(Some(Card::Knife), response.card_shown());
+    }
+
+    #[test]
+    fn test_from_change_set_finds_new_facts() {
+        let mut engine = ClueEngine::load_from_string("36-.6-.6-.3-.").unwrap();
+        let change_set = engine.learn_info_on_card(0, Card::Knife, true, true);
+        let result = WhatIfResult::from_change_set(&engine, &change_set);
+        assert!(result.is_consistent);
+        assert!(result.new_facts.contains(&Fact { player_index: 0, card: Card::Knife, has_card: true }));
+        assert!(result.new_facts.contains(&Fact { player_index: 3, card: Card::Knife, has_card: false }));
+        assert_eq!(vec![Fact { player_index: 3, card: Card::Knife, has_card: false }], result.solution_changes);
+    }
+}
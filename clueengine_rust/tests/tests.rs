@@ -1,8 +1,10 @@
 #[cfg(test)]
 mod tests {
-    use clueengine::{ClueEngine, CardUtils, Card, CardType, CardSet};
+    use clueengine::{ClueEngine, CardUtils, Card, CardType, CardSet, Deal, DealCounter, DeductionChanges, DeductionMode, DeductionRule, Fact, SamplingStrategy, SimulationConfig, SuggestionResponse, Violation};
+    use rand::{rngs::StdRng, SeedableRng};
     use std::collections::HashSet;
     use std::iter::FromIterator;
+    use std::sync::Arc;
 
     #[test]
     fn test_card_type() {
@@ -96,6 +98,86 @@ mod tests {
         assert_load_from_string_then_write_to_string_match("29-AU.9A-BU-CDE-FH.3U-AMNOPQRST.");
     }
 
+    #[test]
+    fn test_write_to_canonical_string_sorts_clauses() {
+        let clue_engine = ClueEngine::load_from_string("29-AU.9A-BU-FH-CDE.3U-AMNOPQRST.").unwrap();
+        assert_eq!("29-AU.9A-BU-CDE-FH.3U-AMNOPQRST.", clue_engine.write_to_canonical_string());
+        let reloaded = ClueEngine::load_from_string(&clue_engine.write_to_canonical_string()).unwrap();
+        assert_eq!(clue_engine.write_to_canonical_string(), reloaded.write_to_canonical_string());
+    }
+
+    #[test]
+    fn test_learn_returns_change_set() {
+        let mut clue_engine = ClueEngine::new(3, None).unwrap();
+        let change_set = clue_engine.learn_suggest(0, Card::ProfessorPlum, Card::Knife, Card::Hall, Some(1), None);
+        assert!(change_set.new_facts.is_empty());
+        assert_eq!(vec![(1, make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Hall]))], change_set.clauses_added);
+
+        let change_set = clue_engine.learn_info_on_card(1, Card::ProfessorPlum, false, true);
+        assert_eq!(vec![Fact { player_index: 1, card: Card::ProfessorPlum, has_card: false }], change_set.new_facts);
+        assert_eq!(vec![(1, make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Hall]))], change_set.clauses_removed);
+        assert_eq!(vec![(1, make_card_set(vec![Card::Knife, Card::Hall]))], change_set.clauses_added);
+
+        // Learning it again doesn't change anything.
+        assert!(clue_engine.learn_info_on_card(1, Card::ProfessorPlum, false, true).is_empty());
+
+        let change_set = clue_engine.learn_info_on_card(3, Card::Knife, true, true);
+        assert_eq!(vec![Card::Knife], change_set.solution_cards);
+        assert!(change_set.new_facts.contains(&Fact { player_index: 3, card: Card::Rope, has_card: false }));
+        assert_eq!(vec![(1, make_card_set(vec![Card::Knife, Card::Hall]))], change_set.clauses_removed);
+        assert!(change_set.clauses_added.is_empty());
+        assert_eq!(Some(true), clue_engine.player_data[1].has_card(Card::Hall));
+    }
+
+    #[test]
+    fn test_validate_finds_card_with_two_owners() {
+        let clue_engine = ClueEngine::load_from_string("36A-.6A-.6-.3-.").unwrap();
+        assert!(clue_engine.validate().contains(&Violation::MultipleOwners { card: Card::ProfessorPlum, player_indices: vec![0, 1] }));
+        assert!(ClueEngine::load_from_string_and_validate("36A-.6A-.6-.3-.").is_err());
+        assert!(ClueEngine::load_from_string_and_validate("36A-.6-.6-.3-.").unwrap().validate().is_empty());
+    }
+
+    #[test]
+    fn test_load_from_string_then_write_to_string_with_cards_shown() {
+        assert_load_from_string_then_write_to_string_match("36-.6-.6-.3-.~1AG~2A");
+    }
+
+    #[test]
+    fn test_load_from_string_cards_shown_to_invalid_player_fails() {
+        assert!(ClueEngine::load_from_string("36-.6-.6-.3-.~3A").is_err());
+    }
+
+    #[test]
+    fn test_load_from_string_then_write_to_string_with_opponent_log() {
+        assert_load_from_string_then_write_to_string_match("36-.6-.6-.3-.~1G|01AGM0G2BHN--");
+    }
+
+    #[test]
+    fn test_load_from_string_bad_opponent_log_fails() {
+        assert!(ClueEngine::load_from_string("36-.6-.6-.3-.|3").is_err());
+        assert!(ClueEngine::load_from_string("36-.6-.6-.3-.|01AGM3-").is_err());
+        assert!(ClueEngine::load_from_string("36-.6-.6-.3-.|01AG").is_err());
+    }
+
+    #[test]
+    fn test_opponent_models_saved_with_engine() {
+        let mut clue_engine = ClueEngine::new(3, None).unwrap();
+        assert!(clue_engine.opponent_models().is_none());
+        clue_engine.track_opponents(0).unwrap();
+        clue_engine.learn_suggest(1, Card::ProfessorPlum, Card::Knife, Card::Hall, Some(0), Some(Card::Knife));
+        clue_engine.learn_suggest(2, Card::MrGreen, Card::Rope, Card::Study, None, None);
+
+        let loaded = ClueEngine::load_from_string(&clue_engine.write_to_string()).unwrap();
+        let models = loaded.opponent_models().unwrap();
+        assert_eq!(0, models.my_player_index());
+        // Only player 1 saw the knife
+        assert_eq!(Some(true), models.notepad(1).unwrap().player_data[0].has_card(Card::Knife));
+        assert_eq!(None, models.notepad(2).unwrap().player_data[0].has_card(Card::Knife));
+        // Everyone saw that nobody could refute player 2
+        assert_eq!(Some(false), models.notepad(1).unwrap().player_data[0].has_card(Card::Rope));
+        assert_eq!(models.remaining_solutions(2), clue_engine.opponent_models().unwrap().remaining_solutions(2));
+    }
+
     fn assert_load_from_string_then_write_to_string_match(s: &str) {
         let clue_engine = ClueEngine::load_from_string(s).unwrap();
         assert_eq!(s, clue_engine.write_to_string());
@@ -221,6 +303,84 @@ mod tests {
         assert_eq!(Some(false), clue_engine.player_data[2].has_card(Card::Knife));
     }
 
+    #[test]
+    fn test_what_if_suggest_does_not_change_engine() {
+        let mut clue_engine = ClueEngine::new(3, None).unwrap();
+        clue_engine.learn_info_on_card(1, Card::ProfessorPlum, false, true);
+        let before = clue_engine.write_to_string();
+
+        let result = clue_engine.what_if_suggest(1, Card::ProfessorPlum, Card::Knife, Card::Hall, SuggestionResponse::NotRefuted);
+
+        assert_eq!(before, clue_engine.write_to_string());
+        assert!(result.is_consistent);
+        assert!(result.new_facts.contains(&Fact { player_index: 0, card: Card::Knife, has_card: false }));
+        assert!(result.solution_changes.contains(&Fact { player_index: 3, card: Card::ProfessorPlum, has_card: true }));
+        assert!(result.solution_changes.contains(&Fact { player_index: 3, card: Card::ColonelMustard, has_card: false }));
+        assert!(!result.solution_changes.iter().any(|fact| fact.player_index != 3));
+    }
+
+    #[test]
+    fn test_what_if_inconsistent() {
+        let mut clue_engine = ClueEngine::new(3, None).unwrap();
+        clue_engine.learn_info_on_card(2, Card::Knife, false, true);
+
+        let result = clue_engine.what_if_suggest(0, Card::ProfessorPlum, Card::Knife, Card::Hall, SuggestionResponse::Refuted { player_index: 2, card_shown: Some(Card::Knife) });
+        assert!(!result.is_consistent);
+        let result = clue_engine.what_if_learn_info_on_card(2, Card::Hall, true);
+        assert!(result.is_consistent);
+        assert!(result.learns_anything());
+    }
+
+    #[test]
+    fn test_possible_suggestion_responses() {
+        let mut clue_engine = ClueEngine::new(4, None).unwrap();
+        clue_engine.learn_info_on_card(1, Card::Knife, false, true);
+        clue_engine.learn_info_on_card(2, Card::Hall, true, true);
+
+        // Player 1 can only show Professor Plum, and player 2 has to refute if player 1 doesn't.
+        let responses = clue_engine.possible_suggestion_responses(0, Card::ProfessorPlum, Card::Knife, Card::Hall, true);
+        assert_eq!(vec![
+            SuggestionResponse::Refuted { player_index: 1, card_shown: Some(Card::ProfessorPlum) },
+            SuggestionResponse::Refuted { player_index: 2, card_shown: Some(Card::ProfessorPlum) },
+            SuggestionResponse::Refuted { player_index: 2, card_shown: Some(Card::Knife) },
+            SuggestionResponse::Refuted { player_index: 2, card_shown: Some(Card::Hall) },
+        ], responses);
+
+        let responses = clue_engine.possible_suggestion_responses(2, Card::ProfessorPlum, Card::Knife, Card::Hall, false);
+        assert_eq!(vec![
+            SuggestionResponse::Refuted { player_index: 3, card_shown: None },
+            SuggestionResponse::Refuted { player_index: 0, card_shown: None },
+            SuggestionResponse::Refuted { player_index: 1, card_shown: None },
+            SuggestionResponse::NotRefuted,
+        ], responses);
+
+        let results = clue_engine.what_if_all_suggestion_responses(2, Card::ProfessorPlum, Card::Knife, Card::Hall, false);
+        assert_eq!(4, results.len());
+        assert!(results.iter().all(|(_, result)| result.is_consistent));
+    }
+
+    #[test]
+    fn test_recommend_card_to_show() {
+        let mut clue_engine = ClueEngine::new(3, None).unwrap();
+        let hand = make_card_set(vec![Card::ProfessorPlum, Card::Knife, Card::Hall, Card::Study, Card::Rope, Card::MrGreen]);
+
+        assert_eq!(None, clue_engine.recommend_card_to_show(&hand, 1, Card::MissScarlet, Card::Wrench, Card::Lounge));
+        assert_eq!(Some(Card::Knife), clue_engine.recommend_card_to_show(&hand, 1, Card::MissScarlet, Card::Knife, Card::Lounge));
+        // Nothing shown yet, so pick the first one
+        assert_eq!(Some(Card::ProfessorPlum), clue_engine.recommend_card_to_show(&hand, 1, Card::ProfessorPlum, Card::Knife, Card::Hall));
+
+        // Player 2 has seen the hall, so show it to them again
+        clue_engine.record_card_shown(2, Card::Hall).unwrap();
+        assert_eq!(Some(Card::Hall), clue_engine.recommend_card_to_show(&hand, 2, Card::ProfessorPlum, Card::Knife, Card::Hall));
+        // Player 1 hasn't seen anything, but it's still better to show a card someone's seen
+        assert_eq!(Some(Card::Hall), clue_engine.recommend_card_to_show(&hand, 1, Card::ProfessorPlum, Card::Knife, Card::Hall));
+        clue_engine.record_card_shown(1, Card::Knife).unwrap();
+        assert_eq!(Some(Card::Knife), clue_engine.recommend_card_to_show(&hand, 1, Card::ProfessorPlum, Card::Knife, Card::Hall));
+
+        assert_eq!(&make_card_set(vec![Card::Knife]), clue_engine.cards_shown_to(1));
+        assert!(clue_engine.record_card_shown(3, Card::Knife).is_err());
+    }
+
     #[test]
     fn test_possible_cards_1() {
         let mut clue_engine = ClueEngine::new(6, None).unwrap();
@@ -325,6 +485,17 @@ mod tests {
         assert_eq!(0, clue_engine.player_data[2].possible_cards.len());
     }
 
+    #[test]
+    fn test_number_card_deduction_leaves_room_for_all_clauses() {
+        let mut clue_engine = ClueEngine::load_from_string("54-BCDKMQ.4M-BCDKQ.4B-ADKLMNR-CQ.3K-ABDGLMNQ.3D-BCKMQ-ALN-AG.3-BDKM.").unwrap();
+        clue_engine.learn_suggest(1, Card::DrOrchid, Card::Candlestick, Card::Kitchen, Some(4), None);
+
+        // Player 4 has one card left for the first two clauses, so it must be
+        // ProfessorPlum, and we don't know which card of the new clause they have.
+        assert_eq!(make_card_set(vec![Card::MissScarlet, Card::ProfessorPlum]), clue_engine.player_data[4].has_cards);
+        assert_eq!(vec![make_card_set(vec![Card::DrOrchid, Card::Candlestick, Card::Kitchen])], clue_engine.player_data[4].possible_cards);
+    }
+
     #[test]
     fn test_eliminate_extra_clauses() {
         let mut clue_engine = ClueEngine::new(6, None).unwrap();
@@ -369,6 +540,237 @@ mod tests {
         assert_eq!(make_usize_set(vec![0, 2, 4, 5, 6]), clue_engine.who_has_card(Card::Hall));
     }
 
+    #[test]
+    fn test_suggest_no_refute_finds_solution() {
+        let mut clue_engine = ClueEngine::new(3, None).unwrap();
+        clue_engine.learn_info_on_card(0, Card::ProfessorPlum, false, true);
+
+        clue_engine.learn_suggest(0, Card::ProfessorPlum, Card::Knife, Card::Hall, None, None);
+
+        // Nobody else can have ProfessorPlum, so it must be in the solution.
+        assert_eq!(Some(true), clue_engine.player_data[clue_engine.number_of_real_players()].has_card(Card::ProfessorPlum));
+        assert_eq!(Some(false), clue_engine.player_data[clue_engine.number_of_real_players()].has_card(Card::MrGreen));
+    }
+
+    #[test]
+    fn test_suggest_passer_leaves_one_owner() {
+        let mut clue_engine = ClueEngine::new(3, None).unwrap();
+        clue_engine.learn_info_on_card(clue_engine.number_of_real_players(), Card::MrGreen, false, true);
+        clue_engine.learn_info_on_card(0, Card::MrGreen, false, true);
+
+        clue_engine.learn_suggest(1, Card::MrGreen, Card::Knife, Card::Hall, Some(0), None);
+
+        assert_eq!(Some(false), clue_engine.player_data[2].has_card(Card::MrGreen));
+        assert_eq!(Some(true), clue_engine.player_data[1].has_card(Card::MrGreen));
+        assert_eq!(make_usize_set(vec![1]), clue_engine.who_has_card(Card::MrGreen));
+    }
+
+    #[test]
+    fn test_single_card_clause_satisfies_other_clauses() {
+        let mut clue_engine = ClueEngine::new(6, None).unwrap();
+        clue_engine.learn_suggest(0, Card::ProfessorPlum, Card::Knife, Card::Hall, Some(2), None);
+        clue_engine.learn_info_on_card(2, Card::Hall, false, true);
+        clue_engine.learn_suggest(0, Card::ProfessorPlum, Card::Rope, Card::Lounge, Some(2), None);
+        clue_engine.learn_info_on_card(2, Card::Lounge, false, true);
+        assert_eq!(2, clue_engine.player_data[2].possible_cards.len());
+
+        clue_engine.learn_info_on_card(2, Card::Knife, false, true);
+
+        // Having ProfessorPlum satisfies both clauses.
+        assert_eq!(Some(true), clue_engine.player_data[2].has_card(Card::ProfessorPlum));
+        assert_eq!(0, clue_engine.player_data[2].possible_cards.len());
+    }
+
+    #[test]
+    fn test_overlapping_clauses_not_identical() {
+        let mut clue_engine = ClueEngine::new(6, None).unwrap();
+        for player_index in [1, 3, 4] {
+            clue_engine.learn_info_on_card(player_index, Card::Hall, false, true);
+        }
+        clue_engine.learn_suggest(0, Card::ProfessorPlum, Card::Knife, Card::Hall, Some(1), None);
+        clue_engine.learn_suggest(2, Card::MrGreen, Card::Knife, Card::Hall, Some(3), None);
+        // The clauses overlap, but two players' clauses still use three cards.
+        assert_eq!(make_usize_set(vec![0, 1, 2, 3, 4, 5, 6]), clue_engine.who_has_card(Card::MrGreen));
+
+        clue_engine.learn_suggest(3, Card::ProfessorPlum, Card::MrGreen, Card::Hall, Some(4), None);
+
+        // Players 1, 3 and 4 need three different cards out of these three
+        assert_eq!(make_usize_set(vec![1, 3, 4]), clue_engine.who_has_card(Card::ProfessorPlum));
+        assert_eq!(make_usize_set(vec![1, 3, 4]), clue_engine.who_has_card(Card::Knife));
+        assert_eq!(make_usize_set(vec![1, 3, 4]), clue_engine.who_has_card(Card::MrGreen));
+    }
+
+    #[test]
+    fn test_default_deduction_rules() {
+        let clue_engine = ClueEngine::new(6, None).unwrap();
+        assert_eq!(vec![("clauses", true), ("card_owner", true), ("solution", true), ("overlapping_clauses", true)], clue_engine.deduction_rules());
+    }
+
+    #[test]
+    fn test_disable_deduction_rule() {
+        let mut clue_engine = ClueEngine::new(6, None).unwrap();
+        clue_engine.set_deduction_rule_enabled("overlapping_clauses", false).unwrap();
+        clue_engine.learn_info_on_card(1, Card::Hall, false, true);
+        clue_engine.learn_info_on_card(3, Card::Hall, false, true);
+        clue_engine.learn_suggest(0, Card::ProfessorPlum, Card::Knife, Card::Hall, Some(1), None);
+        clue_engine.learn_suggest(2, Card::ProfessorPlum, Card::Knife, Card::Hall, Some(3), None);
+
+        // Without the rule we don't know that only 1 and 3 can have these
+        assert_eq!(7, clue_engine.who_has_card(Card::ProfessorPlum).len());
+        assert!(clue_engine.set_deduction_rule_enabled("not_a_rule", false).is_err());
+    }
+
+    // Pretends we have a friend who told us the solution isn't ProfessorPlum
+    // once we know anything about him.
+    #[derive(Debug)]
+    struct NotProfessorPlumRule {
+    }
+
+    impl DeductionRule for NotProfessorPlumRule {
+        fn name(self: &Self) -> &str {
+            return "not_professor_plum";
+        }
+
+        fn deduce(self: &Self, engine: &ClueEngine, changes: &DeductionChanges, deduced_facts: &mut Vec<Fact>) {
+            if changes.cards.contains(&Card::ProfessorPlum) {
+                deduced_facts.push(Fact { player_index: engine.number_of_real_players(), card: Card::ProfessorPlum, has_card: false });
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_deduction_rule() {
+        let mut clue_engine = ClueEngine::new(6, None).unwrap();
+        clue_engine.add_deduction_rule(Arc::new(NotProfessorPlumRule {})).unwrap();
+        assert!(clue_engine.add_deduction_rule(Arc::new(NotProfessorPlumRule {})).is_err());
+
+        let change_set = clue_engine.learn_info_on_card(1, Card::ProfessorPlum, false, true);
+
+        assert_eq!(make_card_set(vec![Card::ProfessorPlum]), change_set.cards());
+        assert_eq!(Some(false), clue_engine.solution_player().has_card(Card::ProfessorPlum));
+    }
+
+    #[test]
+    fn test_learn_entailed_facts_matches_all_deals() {
+        let clue_engine = ClueEngine::load_from_string("54-ABCJNOS.4-ABCJKNOSU.4CNS-ABJLOTU.3J-ABCILNSTU-EHQ.3B-ACJNOS-IU-LT.3A-BCDEFJNS.").unwrap();
+        let mut complete_engine = clue_engine.clone();
+        let change_set = complete_engine.learn_entailed_facts();
+        // The deduction rules miss some of these.
+        assert!(!change_set.is_empty());
+
+        // A fact should be known exactly when there are no consistent deals
+        // where it's false.
+        for player_index in 0..clue_engine.player_data.len() {
+            for card in CardUtils::all_cards().filter(|card| clue_engine.player_data[player_index].has_card(*card) == None) {
+                let number_of_deals_with = |has_card: bool| {
+                    let mut engine_copy = clue_engine.clone();
+                    engine_copy.learn_info_on_card(player_index, card, has_card, false);
+                    DealCounter::new(&engine_copy).map(|deal_counter| deal_counter.total_count()).unwrap_or(0)
+                };
+                let expected = match (number_of_deals_with(true), number_of_deals_with(false)) {
+                    (0, 0) => panic!("No consistent deals!"),
+                    (_, 0) => Some(true),
+                    (0, _) => Some(false),
+                    _ => None
+                };
+                assert_eq!(expected, complete_engine.player_data[player_index].has_card(card), "player {} card {:?}", player_index, card);
+            }
+        }
+    }
+
+    #[test]
+    fn test_complete_deduction_mode() {
+        let mut rules_engine = ClueEngine::load_from_string("63BH-CFGKNRU-EIO.3-BCFGHKNRU-DO.3-BFGHKNOU-CR.3F-BCGHNRU-KO.3GU-BCFHKNR.3N-BCFGHKRU.3-BFGHNU.").unwrap();
+        let mut complete_engine = rules_engine.clone();
+        complete_engine.deduction_mode = DeductionMode::Complete;
+        rules_engine.learn_suggest(1, Card::MrGreen, Card::Knife, Card::Hall, Some(2), Some(Card::Knife));
+        complete_engine.learn_suggest(1, Card::MrGreen, Card::Knife, Card::Hall, Some(2), Some(Card::Knife));
+
+        rules_engine.learn_entailed_facts();
+        assert_eq!(rules_engine.write_to_string(), complete_engine.write_to_string());
+        // Nothing more to learn
+        assert!(complete_engine.learn_entailed_facts().is_empty());
+    }
+
+    #[test]
+    fn test_sample_deals_respects_known_facts() {
+        let mut clue_engine = ClueEngine::new(3, None).unwrap();
+        clue_engine.learn_info_on_card(1, Card::ProfessorPlum, true, true);
+        clue_engine.learn_info_on_card(0, Card::Knife, false, true);
+        clue_engine.learn_suggest(0, Card::MrGreen, Card::Rope, Card::Study, Some(2), None);
+
+        let deals = clue_engine.sample_deals(20, StdRng::seed_from_u64(1)).collect::<Vec<Deal>>();
+
+        assert_eq!(20, deals.len());
+        for deal in deals.iter() {
+            assert_eq!(4, deal.hands.len());
+            for player_index in 0..4 {
+                assert_eq!(clue_engine.player_data[player_index].num_cards.unwrap() as usize, deal.hands[player_index].len());
+            }
+            assert_eq!(Some(1), deal.owner_of_card(Card::ProfessorPlum));
+            assert_ne!(Some(0), deal.owner_of_card(Card::Knife));
+            assert!(deal.hands[2].contains(&Card::MrGreen) || deal.hands[2].contains(&Card::Rope) || deal.hands[2].contains(&Card::Study));
+            for card_type in CardUtils::all_card_types() {
+                assert_eq!(1, deal.solution().iter().filter(|card| CardUtils::card_type(**card) == *card_type).count());
+            }
+            for card in CardUtils::all_cards() {
+                assert!(deal.owner_of_card(card).is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn test_sample_deals_no_possible_solution() {
+        let mut clue_engine = ClueEngine::new(3, None).unwrap();
+        for card in CardUtils::cards_of_type(CardType::Weapon) {
+            clue_engine.learn_info_on_card(3, card, false, false);
+        }
+
+        assert_eq!(0, clue_engine.sample_deals(5, StdRng::seed_from_u64(1)).count());
+    }
+
+    #[test]
+    fn test_simulation_exact_uniform_matches_counts() {
+        let clue_engine = ClueEngine::load_from_string("36-.6--AHT.6-.3-.").unwrap();
+        let deal_counter = DealCounter::new(&clue_engine).unwrap();
+        let lounge_count: u128 = deal_counter.solution_counts().iter().filter(|(solution, _)| solution[2] == Card::Lounge).map(|(_, count)| count).sum();
+        let expected = lounge_count as f64 / deal_counter.total_count() as f64;
+
+        let (simulation_data, total_number_of_simulations) = clue_engine.do_simulation_with_strategy(SamplingStrategy::ExactUniform);
+
+        let lounge_data = simulation_data.get(&Card::Lounge).unwrap();
+        assert_eq!(total_number_of_simulations as usize, lounge_data.iter().sum::<usize>());
+        let actual = lounge_data[3] as f64 / total_number_of_simulations as f64;
+        assert!((actual - expected).abs() < 0.01, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn test_enumerate_deals_nearly_solved() {
+        // We know the solution and all but Kitchen, Library, Ballroom and BilliardRoom
+        let mut clue_engine = ClueEngine::load_from_string("36ABGHM-.6CDIJO-.6EKNQ-.3FLT-.").unwrap();
+        clue_engine.learn_suggest(0, Card::MrsPeacock, Card::Wrench, Card::Kitchen, Some(2), None);
+
+        let (simulation_data, number_of_deals) = clue_engine.enumerate_deals(1000).unwrap();
+
+        // Player 2 must have Kitchen, then the other three rooms go one to each player
+        assert_eq!(6, number_of_deals);
+        assert_eq!(&vec![0, 0, 6, 0], simulation_data.get(&Card::Kitchen).unwrap());
+        assert_eq!(&vec![2, 2, 2, 0], simulation_data.get(&Card::Library).unwrap());
+        assert_eq!(&vec![2, 2, 2, 0], simulation_data.get(&Card::Ballroom).unwrap());
+        assert_eq!(&vec![2, 2, 2, 0], simulation_data.get(&Card::BilliardRoom).unwrap());
+        assert_eq!(&vec![0, 0, 0, 6], simulation_data.get(&Card::Lounge).unwrap());
+
+        let (exhaustive_data, total_number_of_simulations) = clue_engine.do_simulation_with_strategy(SamplingStrategy::Exhaustive);
+        assert_eq!(6, total_number_of_simulations);
+        assert_eq!(simulation_data, exhaustive_data);
+    }
+
+    #[test]
+    fn test_enumerate_deals_too_many() {
+        let clue_engine = ClueEngine::new(3, None).unwrap();
+        assert_eq!(None, clue_engine.enumerate_deals(1000));
+    }
+
     #[test]
     #[ignore] // This test is slow
     fn test_simulation_known_person_has_card() {
@@ -495,4 +897,57 @@ mod tests {
         assert!(solution_plum as f32 > 0.3 * (num_simulations as f32));
     }
 
+    #[test]
+    fn test_simulation_with_config() {
+        let mut clue_engine = ClueEngine::new(6, None).unwrap();
+        clue_engine.learn_info_on_card(1, Card::ProfessorPlum, true, true);
+        // 5 * 6 * 9 = 270 possible solutions, so 10 deals for each one
+        let config = SimulationConfig::builder().num_simulations(2700).chunk_size(4).max_iterations(1).num_threads(2).build().unwrap();
+
+        let (simulation_data, total_number_of_simulations) = clue_engine.do_simulation_with_config(&config);
+        assert_eq!(2700, total_number_of_simulations);
+        let plum_data = simulation_data.get(&Card::ProfessorPlum).unwrap();
+        assert!(plum_data[1] > 0);
+        assert_eq!(plum_data[1], plum_data.iter().sum::<usize>());
+    }
+
+    #[test]
+    fn test_simulate_solutions() {
+        let mut clue_engine = ClueEngine::new(3, None).unwrap();
+        clue_engine.learn_suggest(0, Card::ProfessorPlum, Card::Knife, Card::Hall, None, None);
+        clue_engine.learn_info_on_card(0, Card::Knife, false, true);
+        clue_engine.learn_info_on_card(0, Card::Hall, false, true);
+        let config = SimulationConfig::builder().num_simulations(4000).build().unwrap();
+
+        let distribution = clue_engine.simulate_solutions(&config);
+        // The Knife and the Hall are in the solution, and player 0 might have Professor Plum.
+        assert!(distribution.solutions().iter().all(|(solution, _)| solution[1] == Card::Knife && solution[2] == Card::Hall));
+        let total_probability = distribution.solutions().iter().map(|(_, probability)| probability).sum::<f64>();
+        assert!((total_probability - 1.0).abs() < 1e-9);
+        assert!(distribution.entropy() > 0.0);
+        assert!(distribution.probability(&[Card::ProfessorPlum, Card::Knife, Card::Hall]) > 0.0);
+    }
+
+    #[test]
+    #[ignore] // This test is slow
+    fn test_simulation_joint_data_matches_marginals() {
+        let mut clue_engine = ClueEngine::new(6, None).unwrap();
+        clue_engine.learn_suggest(0, Card::ProfessorPlum, Card::Knife, Card::Hall, Some(5), None);
+
+        let (simulation_data, joint_data, _) = clue_engine.do_simulation_with_joint_data(SamplingStrategy::FixedSolutions);
+
+        let plum_data = simulation_data.get(&Card::ProfessorPlum).unwrap();
+        assert_eq!(plum_data.iter().sum::<usize>(), joint_data.num_simulations());
+        for (player_index, count) in plum_data.iter().enumerate() {
+            assert_eq!(*count, joint_data.count_both(Card::ProfessorPlum, player_index, Card::ProfessorPlum, player_index));
+        }
+        // Player 5 has at least one of these, so having one makes the others less likely
+        assert!(joint_data.correlation(Card::ProfessorPlum, 5, Card::Knife, 5).unwrap() < 0.0);
+        // Only one of each category can be in the solution
+        assert_eq!(0, joint_data.count_both(Card::ProfessorPlum, 6, Card::MrGreen, 6));
+        let solution_hands = joint_data.hand_distribution(6);
+        assert!(solution_hands.iter().all(|(hand, _)| hand.len() == 3));
+        assert_eq!(joint_data.num_simulations(), solution_hands.iter().map(|(_, count)| count).sum::<usize>());
+    }
+
 }
\ No newline at end of file